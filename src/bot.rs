@@ -1,5 +1,22 @@
+mod audio;
+mod autoplay;
 pub mod bot;
+pub mod cache;
 pub mod commands;
 pub mod driver;
+pub mod effects;
+pub mod events;
+mod history;
+mod loudness;
+pub mod lyrics;
+pub mod permissions;
+pub mod playlists;
 pub mod providers;
+pub mod quiz;
+pub mod recorder;
+mod session;
+pub mod settings;
+pub mod soundboard;
+mod speech;
 mod status;
+pub mod track;
@@ -0,0 +1,379 @@
+use serenity::async_trait;
+use songbird::input::codecs::{get_codec_registry, get_probe};
+use songbird::input::{AudioStream, AudioStreamError, AuxMetadata, Compose, Input, RawAdapter};
+use std::f32::consts::PI;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use tracing::warn;
+
+/// A stage that sees every decoded block of interleaved `f32` samples
+/// before songbird mixes and encodes them. Stages may change the block
+/// length, as long as they keep whole frames.
+pub trait Processor: Send + Sync {
+    fn process(&mut self, samples: &mut Vec<f32>, sample_rate: u32, channels: usize);
+}
+
+/// Length of the format header `RawAdapter` puts in front of the samples.
+const RAW_HEADER_LEN: u64 = 16;
+
+/// Builds a fresh processor chain every time the stream is (re)created,
+/// songbird recreates lazy inputs to seek backwards.
+pub type ProcessorFactory = Arc<dyn Fn() -> Vec<Box<dyn Processor>> + Send + Sync>;
+
+/// Sees the provider's stream before it is decoded, and may wrap it.
+pub type SourceTap = Arc<dyn Fn(Box<dyn MediaSource>) -> Box<dyn MediaSource> + Send + Sync>;
+
+/// Wraps a lazy provider input, decoding it ourselves so the processors can
+/// touch the PCM, then hands songbird raw `f32` samples.
+pub struct ProcessedInput {
+    inner: Box<dyn Compose>,
+    processors: ProcessorFactory,
+    tap: Option<SourceTap>,
+}
+
+impl ProcessedInput {
+    /// Live inputs are already being decoded by songbird and pass through untouched.
+    pub fn wrap(input: Input, processors: ProcessorFactory, tap: Option<SourceTap>) -> Input {
+        match input {
+            Input::Lazy(inner) => Input::Lazy(Box::new(ProcessedInput {
+                inner,
+                processors,
+                tap,
+            })),
+            live => {
+                warn!("Cannot process a live input, playing it unprocessed");
+                live
+            }
+        }
+    }
+
+    fn decode(
+        stream: AudioStream<Box<dyn MediaSource>>,
+        processors: ProcessorFactory,
+        tap: Option<SourceTap>,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let hint = stream.hint.unwrap_or_default();
+        let input = match tap {
+            Some(tap) => tap(stream.input),
+            None => stream.input,
+        };
+        let source = MediaSourceStream::new(input, Default::default());
+        let probed = get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        let format = probed.format;
+        let track = format
+            .default_track()
+            .ok_or_else(|| AudioStreamError::Fail("stream has no audio track".into()))?;
+        let decoder = get_codec_registry()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(48_000);
+        let channels = track.codec_params.channels.map_or(2, |c| c.count());
+        let track_id = track.id;
+
+        let decoded = DecodedSource {
+            format,
+            decoder,
+            track_id,
+            sample_rate,
+            channels,
+            chain: processors(),
+            processors,
+            samples: None,
+            block: Vec::new(),
+            pending: Vec::new(),
+            offset: 0,
+            position: 0,
+        };
+        Ok(AudioStream {
+            input: Box::new(RawAdapter::new(decoded, sample_rate, channels as u32)),
+            hint: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Compose for ProcessedInput {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.inner.create()?;
+        Self::decode(stream, self.processors.clone(), self.tap.clone())
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = if self.inner.should_create_async() {
+            self.inner.create_async().await?
+        } else {
+            self.inner.create()?
+        };
+
+        // Probing reads from the network-backed source, which must not happen
+        // on an async worker
+        let processors = self.processors.clone();
+        let tap = self.tap.clone();
+        tokio::task::spawn_blocking(move || Self::decode(stream, processors, tap))
+            .await
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        self.inner.aux_metadata().await
+    }
+}
+
+/// Decodes packets on demand and serves the processed samples as
+/// little-endian `f32` bytes, the layout `RawAdapter` expects. Positions are
+/// in processed samples, which may not line up with the source when an
+/// effect changes the speed, so seeks decode and drop up to the target,
+/// rewinding the source first when going backwards.
+struct DecodedSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    channels: usize,
+    processors: ProcessorFactory,
+    chain: Vec<Box<dyn Processor>>,
+    samples: Option<SampleBuffer<f32>>,
+    block: Vec<f32>,
+    pending: Vec<u8>,
+    offset: usize,
+    // Bytes handed out so far
+    position: u64,
+}
+
+impl DecodedSource {
+    /// Decodes the next packet of our track into `pending`, false at the end of the stream.
+    fn refill(&mut self) -> io::Result<bool> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(false)
+                }
+                Err(SymphoniaError::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(e) => return Err(io::Error::other(e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet is not worth ending the track over
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(io::Error::other(e)),
+            };
+
+            let needed = decoded.capacity() * decoded.spec().channels.count();
+            if self.samples.as_ref().is_none_or(|s| s.capacity() < needed) {
+                self.samples = Some(SampleBuffer::new(
+                    decoded.capacity() as u64,
+                    *decoded.spec(),
+                ));
+            }
+            let samples = self.samples.as_mut().unwrap();
+            samples.copy_interleaved_ref(decoded);
+
+            self.block.clear();
+            self.block.extend_from_slice(samples.samples());
+            for processor in &mut self.chain {
+                processor.process(&mut self.block, self.sample_rate, self.channels);
+            }
+
+            self.pending.clear();
+            self.offset = 0;
+            self.pending
+                .extend(self.block.iter().flat_map(|sample| sample.to_le_bytes()));
+            if !self.pending.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Back to the first sample, with a fresh processor chain.
+    fn rewind(&mut self) -> io::Result<()> {
+        self.format
+            .seek(
+                SeekMode::Coarse,
+                SeekTo::TimeStamp {
+                    ts: 0,
+                    track_id: self.track_id,
+                },
+            )
+            .map_err(io::Error::other)?;
+        self.decoder.reset();
+        self.chain = (self.processors)();
+        self.pending.clear();
+        self.offset = 0;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl Read for DecodedSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset >= self.pending.len() && !self.refill()? {
+            return Ok(0);
+        }
+        let available = &self.pending[self.offset..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.offset += count;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for DecodedSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // `RawAdapter` passes its own offsets through, header included
+        let target = match pos {
+            SeekFrom::Start(target) => target.saturating_sub(RAW_HEADER_LEN),
+            SeekFrom::Current(delta) => self.position.saturating_add_signed(delta),
+            SeekFrom::End(_) => return Err(io::ErrorKind::Unsupported.into()),
+        };
+        if target < self.position {
+            self.rewind()?;
+        }
+
+        let mut scratch = [0u8; 8192];
+        while self.position < target {
+            let len = scratch.len().min((target - self.position) as usize);
+            if self.read(&mut scratch[..len])? == 0 {
+                break;
+            }
+        }
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for DecodedSource {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Direct form I biquad, coefficients normalised by `a0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn from_coefficients(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn omega(frequency: f32, sample_rate: u32, q: f32) -> (f32, f32, f32) {
+        let w0 = 2.0 * PI * frequency / sample_rate as f32;
+        (w0.cos(), w0.sin(), w0.sin() / (2.0 * q))
+    }
+
+    pub fn low_shelf(frequency: f32, sample_rate: u32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, _, alpha) = Self::omega(frequency, sample_rate, q);
+        let sqrt_a = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a),
+            (a + 1.0) + (a - 1.0) * cos + sqrt_a,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - sqrt_a,
+        )
+    }
+
+    pub fn peaking(frequency: f32, sample_rate: u32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, _, alpha) = Self::omega(frequency, sample_rate, q);
+        Self::from_coefficients(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    pub fn high_shelf(frequency: f32, sample_rate: u32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let (cos, _, alpha) = Self::omega(frequency, sample_rate, q);
+        let sqrt_a = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a),
+            (a + 1.0) - (a - 1.0) * cos + sqrt_a,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - sqrt_a,
+        )
+    }
+
+    pub fn high_pass(frequency: f32, sample_rate: u32, q: f32) -> Self {
+        let (cos, _, alpha) = Self::omega(frequency, sample_rate, q);
+        Self::from_coefficients(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
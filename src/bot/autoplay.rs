@@ -0,0 +1,94 @@
+use super::bot::Error;
+use super::track::{track_key, youtube_id, QueuedTrack};
+
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use songbird::input::YoutubeDl;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+
+/// How many entries of YouTube's mix playlist to consider.
+const MIX_LENGTH: usize = 25;
+const SEARCH_RESULTS: usize = 10;
+
+/// One entry of `yt-dlp --flat-playlist -j`.
+#[derive(Deserialize)]
+struct FlatEntry {
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    channel: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+}
+
+/// Candidates to play after `seed`, best first. Uses YouTube's mix playlist
+/// for YouTube links and falls back to searching for the same artist.
+pub async fn related(
+    http_client: HttpClient,
+    seed: &QueuedTrack,
+) -> Result<Vec<QueuedTrack>, Error> {
+    if let Some(id) = youtube_id(&seed.url) {
+        match youtube_mix(&id).await {
+            Ok(tracks) if !tracks.is_empty() => return Ok(tracks),
+            Ok(_) => {}
+            Err(e) => warn!("Could not load YouTube mix for {id}: {e}"),
+        }
+    }
+
+    let query = seed
+        .artist
+        .as_ref()
+        .or(seed.title.as_ref())
+        .ok_or("The last track has no metadata to search with")?;
+    let mut search = YoutubeDl::new_search(http_client, query.clone());
+    let tracks = search
+        .search(Some(SEARCH_RESULTS))
+        .await?
+        .filter_map(|metadata| {
+            let url = metadata.source_url.clone()?;
+            Some(QueuedTrack::autoplay(url).with_metadata(&metadata))
+        })
+        .collect();
+    Ok(tracks)
+}
+
+/// First candidate that isn't in `exclude`, compared by `track_key`.
+pub fn pick(candidates: Vec<QueuedTrack>, exclude: &HashSet<String>) -> Option<QueuedTrack> {
+    candidates
+        .into_iter()
+        .find(|track| !exclude.contains(&track_key(&track.url)))
+}
+
+async fn youtube_mix(id: &str) -> Result<Vec<QueuedTrack>, Error> {
+    let mix = format!("https://www.youtube.com/watch?v={id}&list=RD{id}");
+    let output = Command::new("yt-dlp")
+        .args(["--flat-playlist", "-j", "--playlist-end"])
+        .arg(MIX_LENGTH.to_string())
+        .arg(&mix)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+    }
+
+    let tracks = output
+        .stdout
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| serde_json::from_slice::<FlatEntry>(line).ok())
+        .map(|entry| {
+            let url = entry
+                .url
+                .unwrap_or_else(|| format!("https://www.youtube.com/watch?v={}", entry.id));
+            let mut track = QueuedTrack::autoplay(url);
+            track.title = entry.title;
+            track.artist = entry.channel.or(entry.uploader);
+            track.duration = entry.duration.map(Duration::from_secs_f64);
+            track
+        })
+        .collect();
+    Ok(tracks)
+}
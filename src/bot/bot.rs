@@ -1,10 +1,26 @@
 use crate::bot::providers::Providers;
+use crate::tarkov::client::{TarkovClient, API_URL};
+use crate::tarkov::data::TarkovData;
+use crate::tarkov::progress::QuestProgress;
 
+use super::cache::AudioCache;
 use super::commands;
 use super::driver::Driver;
+use super::lyrics::{Lrclib, LyricsProvider};
+use super::playlists::Playlists;
+use super::quiz::Quiz;
+use super::recorder::Recorder;
+use super::settings::Settings;
+use super::soundboard::Soundboard;
+use super::speech;
+use super::track::QueuedTrack;
 use poise::structs::Command;
 use reqwest::Client as HttpClient;
-use songbird::input::Input;
+use serenity::all::{GuildId, UserId};
+use songbird::input::File;
+use songbird::Songbird;
+use std::sync::Arc;
+use tracing::warn;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::PrefixContext<'a, Bot, Error>;
@@ -12,13 +28,39 @@ pub type Context<'a> = poise::PrefixContext<'a, Bot, Error>;
 pub struct Bot {
     pub http_client: HttpClient,
     pub driver: Driver,
+    pub settings: Settings,
+    pub playlists: Playlists,
+    pub cache: AudioCache,
+    pub soundboard: Soundboard,
+    pub recorder: Recorder,
+    pub quiz: Quiz,
+    pub lyrics: Arc<dyn LyricsProvider>,
+    pub tarkov: TarkovData,
+    pub quest_progress: QuestProgress,
 }
 
 impl Bot {
     pub fn new() -> Self {
+        let http_client = HttpClient::new();
+        let settings = Settings::new();
+        let cache = AudioCache::new();
         Self {
-            http_client: HttpClient::new(),
-            driver: Driver::new(),
+            driver: Driver::new(
+                http_client.clone(),
+                settings.clone(),
+                cache.clone(),
+                speech::from_env(),
+            ),
+            lyrics: Arc::new(Lrclib::new(http_client.clone())),
+            tarkov: TarkovData::new(TarkovClient::new(http_client.clone(), API_URL)),
+            quest_progress: QuestProgress::new(),
+            http_client,
+            settings,
+            playlists: Playlists::new(),
+            cache,
+            soundboard: Soundboard::new(),
+            recorder: Recorder::default(),
+            quiz: Quiz::default(),
         }
     }
 
@@ -30,30 +72,104 @@ impl Bot {
             commands::skip(),
             commands::join(),
             commands::leave(),
+            commands::volume(),
+            commands::loop_mode(),
+            commands::resume_session(),
+            commands::voteskip(),
+            commands::perm(),
+            commands::limits(),
+            commands::fair(),
+            commands::history(),
+            commands::back(),
+            commands::replay(),
+            commands::autoplay(),
+            commands::nowplaying(),
+            commands::playlist(),
+            commands::export(),
+            commands::import(),
+            commands::normalize(),
+            commands::fx(),
+            commands::crossfade(),
+            commands::cache(),
+            commands::sb(),
+            commands::say(),
+            commands::announce(),
+            commands::record(),
+            commands::quiz(),
+            commands::lyrics(),
+            commands::quest(),
+            commands::kappa(),
+            commands::price(),
+            commands::ammo(),
+            commands::hideout(),
+            commands::crafts(),
+            commands::tarkov(),
         ]
     }
 
-    pub async fn play_input(&self, user_input: String) -> Result<(), Error> {
+    pub async fn play_input(&self, user_input: String, requester: UserId) -> Result<(), Error> {
+        let track = self.resolve_track(user_input, requester).await?;
+        self.driver.enqueue_input(track).await
+    }
+
+    /// Plays a soundboard clip over the music for `user`, subject to their cooldown.
+    pub async fn play_clip(
+        &self,
+        manager: Arc<Songbird>,
+        guild_id: GuildId,
+        user: UserId,
+        name: &str,
+    ) -> Result<(), Error> {
+        if self.driver.guild() != Some(guild_id) {
+            return Err("Not connected in a voice channel, use !join to connect".into());
+        }
+        let path = self
+            .soundboard
+            .path(guild_id, name)
+            .ok_or(format!("There is no clip named {name}"))?;
+        self.soundboard.check_cooldown(user)?;
+
+        let volume = self.soundboard.volume(guild_id, name);
+        let handle = self
+            .driver
+            .play_overlay(manager, File::new(path).into(), volume)
+            .await?;
+        // A clip that fails to open doesn't count against the cooldown
+        handle.make_playable_async().await?;
+        self.soundboard.start_cooldown(user);
+        Ok(())
+    }
+
+    /// Turns a link or search query into a track with its metadata resolved.
+    pub async fn resolve_track(
+        &self,
+        user_input: String,
+        requester: UserId,
+    ) -> Result<QueuedTrack, Error> {
         let providers = Providers::all();
-        let mut stream: Option<Input> = None;
+        let mut url: Option<String> = None;
 
-        if let Some(provider) = providers.iter().find(|p| p.is_valid(&user_input)) {
-            stream = Some(provider.get_stream(self.http_client.clone(), user_input));
+        if providers.iter().any(|p| p.is_valid(&user_input)) {
+            url = Some(user_input);
         } else {
             for provider in providers {
                 let results = provider.search(&user_input).await?;
-                if let Some(result) = results.get(0) {
-                    stream = Some(provider.get_stream(self.http_client.clone(), result.clone()));
+                if let Some(result) = results.first() {
+                    url = Some(result.clone());
                     break;
                 }
             }
         }
 
-        if let Some(stream) = stream {
-            self.driver.enqueue_input(stream).await?;
-            return Ok(());
+        if let Some(url) = url {
+            let mut track = QueuedTrack::new(url.clone(), requester);
+            match Providers::metadata(self.http_client.clone(), url).await {
+                Ok(metadata) => track = track.with_metadata(&metadata),
+                Err(e) => warn!("Could not fetch track metadata: {e}"),
+            }
+            return Ok(track);
         }
 
-        return Err("No valid stream found".into());
+        Err("No valid stream found".into())
     }
 }
@@ -0,0 +1,518 @@
+use super::bot::Error;
+use crate::storage::JsonStore;
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use symphonia::core::io::MediaSource;
+use tracing::{info, warn};
+
+const CACHE_DIR: &str = "data/cache";
+/// Size cap in megabytes when `AUDIO_CACHE_MB` is not set.
+const DEFAULT_MAX_MB: u64 = 1024;
+/// Above what a voice channel plays at, so the cache costs no quality.
+const OPUS_BITRATE: &str = "128k";
+
+/// Turns the stream copied to the first path into the cached file at the second.
+type Transcoder = Arc<dyn Fn(&Path, &Path) -> Result<(), Error> + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    file: String,
+    size: u64,
+    last_used: u64,
+    plays: u64,
+}
+
+pub struct CacheStats {
+    pub entries: usize,
+    pub size: u64,
+    pub max_size: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Tracks that played through, transcoded to Opus in Ogg and keyed by
+/// `track_key`, so replays skip yt-dlp. Least recently used files go first
+/// once the cache outgrows its cap.
+#[derive(Clone)]
+pub struct AudioCache {
+    dir: PathBuf,
+    index: JsonStore<HashMap<String, CacheEntry>>,
+    max_size: u64,
+    transcode: Transcoder,
+    // Lookups since startup
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    // Keys with a stream being copied in or transcoded right now
+    writing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl AudioCache {
+    pub fn new() -> Self {
+        let max_mb = env::var("AUDIO_CACHE_MB")
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .unwrap_or(DEFAULT_MAX_MB);
+        Self::with_dir(
+            PathBuf::from(CACHE_DIR),
+            max_mb * 1024 * 1024,
+            Arc::new(to_opus),
+        )
+    }
+
+    fn with_dir(dir: PathBuf, max_size: u64, transcode: Transcoder) -> Self {
+        Self {
+            index: JsonStore::new(dir.join("index.json")),
+            dir,
+            max_size,
+            transcode,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+            writing: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Path of the cached file for `key`, marking it as recently used.
+    pub fn lookup(&self, key: &str) -> Option<PathBuf> {
+        let path = self
+            .index
+            .update(|index| {
+                let path = index
+                    .get(key)
+                    .map(|entry| self.dir.join(&entry.file))
+                    .filter(|path| path.exists());
+                match path {
+                    Some(_) => {
+                        let entry = index.get_mut(key).unwrap();
+                        entry.last_used = now();
+                        entry.plays += 1;
+                    }
+                    // Deleted behind our back, forget it
+                    None => {
+                        index.remove(key);
+                    }
+                }
+                path
+            })
+            .unwrap_or_else(|e| {
+                warn!("Could not read the audio cache index: {e}");
+                None
+            });
+
+        let counter = if path.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        path
+    }
+
+    /// Wraps the stream `key` is about to play from so it is copied into the
+    /// cache as it is read. Streams already cached or being copied come back
+    /// untouched.
+    pub fn tee(&self, key: &str, source: Box<dyn MediaSource>) -> Box<dyn MediaSource> {
+        let cached = self
+            .index
+            .load()
+            .map(|index| index.contains_key(key))
+            .unwrap_or(false);
+        if cached || !self.writing.lock().unwrap().insert(key.to_string()) {
+            return source;
+        }
+
+        let partial = self.dir.join(format!("{}.partial", file_stem(key)));
+        let file = fs::create_dir_all(&self.dir).and_then(|_| fs::File::create(&partial));
+        match file {
+            Ok(file) => Box::new(TeeSource {
+                inner: source,
+                cache: self.clone(),
+                key: key.to_string(),
+                partial,
+                file: Some(BufWriter::new(file)),
+                position: 0,
+                written: 0,
+                handed_over: false,
+            }),
+            Err(e) => {
+                warn!("Could not start caching {key}: {e}");
+                self.writing.lock().unwrap().remove(key);
+                source
+            }
+        }
+    }
+
+    /// Transcodes a complete copy of `key`'s stream into the cache.
+    fn store(&self, key: &str, partial: &Path) -> Result<(), Error> {
+        let file = format!("{}.ogg", file_stem(key));
+        let path = self.dir.join(&file);
+        let transcoded = (self.transcode)(partial, &path);
+        let _ = fs::remove_file(partial);
+        if let Err(e) = transcoded {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        let size = fs::metadata(&path)?.len();
+        self.index.update(|index| {
+            index.insert(
+                key.to_string(),
+                CacheEntry {
+                    file,
+                    size,
+                    last_used: now(),
+                    plays: 0,
+                },
+            );
+        })?;
+        info!("Cached {key} ({} KiB)", size / 1024);
+        self.evict()
+    }
+
+    /// Drops least recently used files until the cache fits its cap.
+    fn evict(&self) -> Result<(), Error> {
+        let evicted = self.index.update(|index| {
+            let mut total: u64 = index.values().map(|entry| entry.size).sum();
+            let mut by_age = index
+                .iter()
+                .map(|(key, entry)| (entry.last_used, key.clone()))
+                .collect::<Vec<_>>();
+            by_age.sort();
+
+            let mut evicted = Vec::new();
+            for (_, key) in by_age {
+                if total <= self.max_size {
+                    break;
+                }
+                if let Some(entry) = index.remove(&key) {
+                    total -= entry.size;
+                    evicted.push(entry.file);
+                }
+            }
+            evicted
+        })?;
+
+        for file in evicted {
+            if let Err(e) = fs::remove_file(self.dir.join(&file)) {
+                warn!("Could not remove evicted {file}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, Error> {
+        let index = self.index.load()?;
+        Ok(CacheStats {
+            entries: index.len(),
+            size: index.values().map(|entry| entry.size).sum(),
+            max_size: self.max_size,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Deletes every cached file, returning how many there were and their size.
+    pub fn purge(&self) -> Result<(usize, u64), Error> {
+        let removed = self.index.update(std::mem::take)?;
+        for entry in removed.values() {
+            if let Err(e) = fs::remove_file(self.dir.join(&entry.file)) {
+                warn!("Could not remove cached {}: {e}", entry.file);
+            }
+        }
+        Ok((
+            removed.len(),
+            removed.values().map(|entry| entry.size).sum(),
+        ))
+    }
+}
+
+/// Copies the bytes read from a track's stream to a partial file, and hands
+/// it to the cache to transcode once everything up to the end of the stream
+/// was copied. Seeks backwards are fine, a copy with a gap is dropped.
+struct TeeSource {
+    inner: Box<dyn MediaSource>,
+    cache: AudioCache,
+    key: String,
+    partial: PathBuf,
+    // None once the copy was stored or given up
+    file: Option<BufWriter<fs::File>>,
+    position: u64,
+    // Bytes copied, always the start of the stream
+    written: u64,
+    // Set once the partial file belongs to the transcoding thread
+    handed_over: bool,
+}
+
+impl TeeSource {
+    fn copy(&mut self, bytes: &[u8]) {
+        let end = self.position + bytes.len() as u64;
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        if self.position > self.written || end <= self.written {
+            return;
+        }
+        let new = &bytes[(self.written - self.position) as usize..];
+        if let Err(e) = file.write_all(new) {
+            warn!("Could not cache {}: {e}", self.key);
+            self.file = None;
+            return;
+        }
+        self.written = end;
+    }
+
+    fn finish(&mut self) {
+        let Some(file) = self.file.take() else {
+            return;
+        };
+        let flushed = file
+            .into_inner()
+            .map_err(|e| Error::from(e.into_error()))
+            .and_then(|file| Ok(file.sync_all()?));
+        if let Err(e) = flushed {
+            warn!("Could not cache {}: {e}", self.key);
+            return;
+        }
+
+        // Transcoding takes a while, the track ends without waiting for it
+        self.handed_over = true;
+        let cache = self.cache.clone();
+        let key = self.key.clone();
+        let partial = self.partial.clone();
+        thread::spawn(move || {
+            if let Err(e) = cache.store(&key, &partial) {
+                warn!("Could not cache {key}: {e}");
+            }
+            cache.writing.lock().unwrap().remove(&key);
+        });
+    }
+}
+
+impl Read for TeeSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.copy(&buf[..read]);
+        self.position += read as u64;
+        let at_end = (read == 0 && !buf.is_empty()) || self.inner.byte_len() == Some(self.position);
+        if at_end && self.position == self.written {
+            self.finish();
+        }
+        Ok(read)
+    }
+}
+
+impl Seek for TeeSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for TeeSource {
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.inner.byte_len()
+    }
+}
+
+impl Drop for TeeSource {
+    fn drop(&mut self) {
+        if self.handed_over {
+            return;
+        }
+        // Copies never finished or given up leave nothing behind
+        self.file = None;
+        let _ = fs::remove_file(&self.partial);
+        self.cache.writing.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Transcodes whatever container the provider sent to Opus in Ogg.
+fn to_opus(input: &Path, output: &Path) -> Result<(), Error> {
+    let result = Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-vn", "-c:a", "libopus", "-b:a", OPUS_BITRATE, "-f", "ogg"])
+        .arg(output)
+        .output()?;
+    if !result.status.success() {
+        return Err(String::from_utf8_lossy(&result.stderr).into_owned().into());
+    }
+    Ok(())
+}
+
+/// A file name for `key` that is safe on any filesystem. The hash keeps
+/// keys that only differ in stripped characters apart.
+fn file_stem(key: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let readable = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(64)
+        .collect::<String>();
+    format!("{readable}-{:016x}", hasher.finish())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    /// Stands in for ffmpeg, the stored file is the stream as it was read.
+    fn copy(input: &Path, output: &Path) -> Result<(), Error> {
+        fs::copy(input, output)?;
+        Ok(())
+    }
+
+    fn cache(name: &str, max_size: u64) -> AudioCache {
+        let dir = env::temp_dir().join(format!("mee6-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        AudioCache::with_dir(dir, max_size, Arc::new(copy))
+    }
+
+    /// Waits for transcoding in the background to finish.
+    fn settle(cache: &AudioCache) {
+        for _ in 0..500 {
+            if cache.writing.lock().unwrap().is_empty() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("caching never finished");
+    }
+
+    fn play(cache: &AudioCache, key: &str, bytes: Vec<u8>) -> Vec<u8> {
+        let mut source = cache.tee(key, Box::new(Cursor::new(bytes)));
+        let mut read = Vec::new();
+        source.read_to_end(&mut read).unwrap();
+        drop(source);
+        settle(cache);
+        read
+    }
+
+    fn keys(cache: &AudioCache) -> Vec<String> {
+        let mut keys = cache.index.load().unwrap().into_keys().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn caches_what_played() {
+        let cache = cache("tee", 1024);
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(play(&cache, "yt:a", bytes.clone()), bytes);
+
+        let path = cache.lookup("yt:a").unwrap();
+        assert_eq!(fs::read(path).unwrap(), bytes);
+        assert!(cache.writing.lock().unwrap().is_empty());
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.entries, stats.size, stats.hits), (1, 256, 1));
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn drops_copies_with_gaps() {
+        let cache = cache("gap", 1024);
+        let mut source = cache.tee("yt:a", Box::new(Cursor::new(vec![1; 64])));
+        let mut buf = [0; 16];
+        source.read_exact(&mut buf).unwrap();
+        source.seek(SeekFrom::Start(32)).unwrap();
+        source.read_to_end(&mut Vec::new()).unwrap();
+        drop(source);
+        assert!(cache.lookup("yt:a").is_none());
+
+        // Going back over what was copied is fine
+        let mut source = cache.tee("yt:b", Box::new(Cursor::new(vec![2; 64])));
+        source.read_exact(&mut buf).unwrap();
+        source.seek(SeekFrom::Start(0)).unwrap();
+        source.read_to_end(&mut Vec::new()).unwrap();
+        drop(source);
+        settle(&cache);
+        assert_eq!(
+            fs::read(cache.lookup("yt:b").unwrap()).unwrap(),
+            vec![2; 64]
+        );
+        assert_eq!(keys(&cache), ["yt:b"]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = cache("lru", 250);
+        for key in ["yt:a", "yt:b"] {
+            play(&cache, key, vec![0; 100]);
+        }
+        // Touch a so b is the oldest
+        cache
+            .index
+            .update(|index| {
+                index.get_mut("yt:a").unwrap().last_used += 10;
+            })
+            .unwrap();
+        play(&cache, "yt:c", vec![0; 100]);
+
+        assert_eq!(keys(&cache), ["yt:a", "yt:c"]);
+        assert_eq!(cache.stats().unwrap().size, 200);
+        let files = fs::read_dir(&cache.dir)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|extension| extension == "ogg")
+            })
+            .count();
+        assert_eq!(files, 2);
+
+        assert_eq!(cache.purge().unwrap(), (2, 200));
+        assert_eq!(cache.stats().unwrap().size, 0);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn failed_transcodes_leave_nothing() {
+        let dir = env::temp_dir().join(format!("mee6-cache-fail-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = AudioCache::with_dir(
+            dir,
+            1024,
+            Arc::new(|_: &Path, output: &Path| {
+                fs::write(output, b"half")?;
+                Err("no encoder".into())
+            }),
+        );
+        play(&cache, "yt:a", vec![0; 64]);
+
+        assert!(cache.lookup("yt:a").is_none());
+        let files = fs::read_dir(&cache.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(files, ["index.json"]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
@@ -1,28 +1,125 @@
-use super::bot::{Context, Error};
+use super::bot::{Bot, Context, Error};
+use super::driver::{format_duration, SkipVote, MAX_CROSSFADE_SECS};
+use super::effects::Effect;
+use super::lyrics::{self, Lyrics};
+use super::permissions::{self, Access};
+use super::playlists::formats::{self, Format};
+use super::playlists::{Playlist, Scope};
+use super::quiz::{self, DEFAULT_ROUNDS, MAX_ROUNDS};
+use super::recorder::{FinishedRecording, BYTES_PER_SECOND, MAX_RECORDING};
+use super::soundboard;
+use super::speech::MAX_SPEECH_CHARS;
+use super::status::LoopMode;
+use super::track::track_key;
+use crate::tarkov::hideout::{self, split_level};
+use crate::tarkov::progress::{self, MAX_LOYALTY_LEVEL, MAX_PLAYER_LEVEL};
+use crate::tarkov::search;
+use crate::tarkov::types::Quest;
+use crate::tarkov::utils::{
+    crafts_list, item_embed, item_menu, progress_bar, quest_menu, station_embed, task_embed,
+    MAX_MENU_CHOICES,
+};
+use crate::tarkov::{ammo, chart};
+use crate::text;
 
+use poise::CreateReply;
+use serenity::all::{
+    ChannelId, Colour, CreateAllowedMentions, CreateAttachment, CreateEmbed, CreateEmbedFooter,
+    CreateMessage, Http, Role, UserId,
+};
 use serenity::model::mention::Mentionable;
+use songbird::CoreEvent;
+use std::collections::HashSet;
+use std::time::UNIX_EPOCH;
 
-use tracing::{debug, info};
+use tracing::{info, warn};
 
-#[poise::command(prefix_command, user_cooldown = 10, aliases("check", "ustraight"))]
+#[poise::command(
+    prefix_command,
+    user_cooldown = 10,
+    aliases("check", "ustraight"),
+    check = "permissions::check"
+)]
 pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
     ctx.say("Pong!").await?;
     Ok(())
 }
 
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
 pub async fn skip(ctx: Context<'_>) -> Result<(), Error> {
     info!("!SKIP invoked by {:?}", &ctx.author().name,);
-    ctx.data.driver.skip_current_track().await
+
+    let author = ctx.author().id;
+    let bot_channel = ctx.data.driver.channel();
+    let listeners: HashSet<UserId> = {
+        let guild = ctx.guild().unwrap();
+        guild
+            .voice_states
+            .values()
+            .filter(|voice_state| voice_state.channel_id.is_some())
+            .filter(|voice_state| voice_state.channel_id == bot_channel)
+            .filter(|voice_state| !voice_state.member.as_ref().is_some_and(|m| m.user.bot))
+            .map(|voice_state| voice_state.user_id)
+            .collect()
+    };
+
+    let is_dj = permissions::author_access(poise::Context::Prefix(ctx)).await >= Access::Dj;
+    if is_dj || ctx.data.driver.current_requester() == Some(author) {
+        return ctx.data.driver.skip_current_track().await;
+    }
+
+    let percent = ctx
+        .data
+        .settings
+        .get(ctx.guild_id().unwrap())
+        .vote_skip_percent;
+    match ctx
+        .data
+        .driver
+        .vote_skip(author, &listeners, percent)
+        .await?
+    {
+        SkipVote::Skipped => {
+            ctx.msg.react(&ctx.http(), '⏭').await?;
+        }
+        SkipVote::Registered { votes, needed } => {
+            ctx.say(format!("Skip vote registered ({votes}/{needed})"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn voteskip(ctx: Context<'_>, percent: Option<u8>) -> Result<(), Error> {
+    info!("VOTESKIP invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(percent) = percent else {
+        let current = ctx.data.settings.get(guild_id).vote_skip_percent;
+        ctx.say(format!("Skipping takes {current}% of listeners"))
+            .await?;
+        return Ok(());
+    };
+
+    if !(1..=100).contains(&percent) {
+        ctx.say("Threshold must be between 1 and 100").await?;
+        return Ok(());
+    }
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.vote_skip_percent = percent)?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
 }
 
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, check = "permissions::check")]
 pub async fn pause(ctx: Context<'_>) -> Result<(), Error> {
     info!("PAUSE invoked by {:?}", &ctx.author().name);
     ctx.data.driver.pause_current_track().await
 }
 
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, check = "permissions::check")]
 pub async fn join(ctx: Context<'_>) -> Result<(), Error> {
     info!("!JOIN by {:?}", &ctx.author().name,);
 
@@ -50,22 +147,16 @@ pub async fn join(ctx: Context<'_>) -> Result<(), Error> {
         .expect("Songbird voice client err")
         .clone();
 
-    let call = manager
-        .join(guild_id, channel_id.unwrap())
-        .await
-        .expect("Could not get discord call");
-
-    let driver = ctx.data().driver.clone();
-
-    tokio::spawn(async move {
-        driver.player(call).await;
-    });
+    ctx.data()
+        .driver
+        .connect(manager, guild_id, channel_id.unwrap())
+        .await?;
 
     ctx.msg.react(&ctx.http(), '👀').await?;
     Ok(())
 }
 
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, check = "permissions::check")]
 pub async fn leave(ctx: Context<'_>) -> Result<(), Error> {
     info!("LEAVE invoked by {:?}", &ctx.author().name,);
 
@@ -78,7 +169,11 @@ pub async fn leave(ctx: Context<'_>) -> Result<(), Error> {
     ctx.data.driver.leave(manager, guild_id).await
 }
 
-#[poise::command(prefix_command, aliases("p", "queue", "q"))]
+#[poise::command(
+    prefix_command,
+    aliases("p", "queue", "q"),
+    check = "permissions::check"
+)]
 pub async fn play(ctx: Context<'_>, #[rest] argument: Option<String>) -> Result<(), Error> {
     info!("PLAY invoked by {:?}", &ctx.author().name);
 
@@ -87,7 +182,1862 @@ pub async fn play(ctx: Context<'_>, #[rest] argument: Option<String>) -> Result<
     }
     let link = argument.unwrap();
 
-    ctx.data.play_input(link).await?;
+    ctx.data.play_input(link, ctx.author().id).await?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, aliases("vol"), check = "permissions::check")]
+pub async fn volume(ctx: Context<'_>, percent: Option<u32>) -> Result<(), Error> {
+    info!("VOLUME invoked by {:?}", &ctx.author().name);
+
+    match percent {
+        None => {
+            let current = (ctx.data.driver.volume() * 100.0).round();
+            ctx.say(format!("Volume is at {current}%")).await?;
+        }
+        Some(percent) if percent > 200 => {
+            ctx.say("Volume can go up to 200%").await?;
+        }
+        Some(percent) => {
+            ctx.data.driver.set_volume(percent as f32 / 100.0)?;
+            ctx.msg.react(&ctx.http(), '✅').await?;
+        }
+    }
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "loop", check = "permissions::check")]
+pub async fn loop_mode(ctx: Context<'_>, mode: Option<String>) -> Result<(), Error> {
+    info!("LOOP invoked by {:?}", &ctx.author().name);
+
+    let Some(mode) = mode else {
+        ctx.say(format!("Loop mode is {:?}", ctx.data.driver.loop_mode()))
+            .await?;
+        return Ok(());
+    };
+
+    ctx.data.driver.set_loop_mode(mode.parse::<LoopMode>()?);
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    rename = "resume-session",
+    guild_only,
+    check = "permissions::check"
+)]
+pub async fn resume_session(ctx: Context<'_>) -> Result<(), Error> {
+    info!("RESUME-SESSION invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Could not get songbird client")
+        .clone();
+
+    ctx.data.driver.resume_session(manager, guild_id).await?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    check = "permissions::check",
+    subcommands("perm_list", "perm_set", "perm_reset", "perm_dj")
+)]
+pub async fn perm(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use !perm list, !perm set <command> <anyone|dj|admin>, !perm reset <command> or !perm dj <role>")
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "list", check = "permissions::check")]
+pub async fn perm_list(ctx: Context<'_>) -> Result<(), Error> {
+    info!("PERM LIST invoked by {:?}", &ctx.author().name);
+
+    let settings = ctx.data.settings.get(ctx.guild_id().unwrap());
+    let dj_role = match settings.dj_role {
+        Some(role_id) => role_id.mention().to_string(),
+        None => format!("roles named {}", permissions::DJ_ROLE),
+    };
+
+    let mut message = format!("**DJ role:** {dj_role}\n");
+    for command in &ctx.framework.options().commands {
+        let access = permissions::required_access(&settings, &command.name);
+        let marker = if settings.permissions.contains_key(&command.name) {
+            " (custom)"
+        } else {
+            ""
+        };
+        message.push_str(&format!("`!{}` {access}{marker}\n", command.name));
+    }
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "set", check = "permissions::check")]
+pub async fn perm_set(ctx: Context<'_>, command: String, access: String) -> Result<(), Error> {
+    info!("PERM SET invoked by {:?}", &ctx.author().name);
+
+    let name = command.trim_start_matches('!').to_lowercase();
+    let Some(command) = command_name(ctx, &name) else {
+        ctx.say(format!("There is no !{name} command")).await?;
+        return Ok(());
+    };
+    let access = access.parse::<Access>()?;
+
+    ctx.data
+        .settings
+        .update(ctx.guild_id().unwrap(), |settings| {
+            settings.permissions.insert(command, access);
+        })?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+/// The name overrides are stored under for the command `name` or one of
+/// its aliases invokes.
+fn command_name(ctx: Context<'_>, name: &str) -> Option<String> {
+    ctx.framework
+        .options()
+        .commands
+        .iter()
+        .find(|c| c.name == name || c.aliases.iter().any(|alias| alias == name))
+        .map(|c| c.name.clone())
+}
+
+#[poise::command(prefix_command, rename = "reset", check = "permissions::check")]
+pub async fn perm_reset(ctx: Context<'_>, command: String) -> Result<(), Error> {
+    info!("PERM RESET invoked by {:?}", &ctx.author().name);
+
+    let name = command.trim_start_matches('!').to_lowercase();
+    // Unknown names still go, so overrides of removed commands can be cleared
+    let command = command_name(ctx, &name).unwrap_or(name);
+    ctx.data
+        .settings
+        .update(ctx.guild_id().unwrap(), |settings| {
+            settings.permissions.remove(&command);
+        })?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "dj", check = "permissions::check")]
+pub async fn perm_dj(ctx: Context<'_>, role: Option<Role>) -> Result<(), Error> {
+    info!("PERM DJ invoked by {:?}", &ctx.author().name);
+
+    ctx.data
+        .settings
+        .update(ctx.guild_id().unwrap(), |settings| {
+            settings.dj_role = role.map(|role| role.id);
+        })?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn limits(
+    ctx: Context<'_>,
+    kind: Option<String>,
+    value: Option<String>,
+) -> Result<(), Error> {
+    info!("LIMITS invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let (Some(kind), Some(value)) = (kind, value) else {
+        let settings = ctx.data.settings.get(guild_id);
+        let tracks = settings
+            .max_tracks_per_user
+            .map_or("no limit".to_string(), |limit| limit.to_string());
+        let length = settings
+            .max_track_length_secs
+            .map_or("no limit".to_string(), format_duration);
+        ctx.say(format!(
+            "**Tracks per user:** {tracks}\n**Track length:** {length}\n\
+            Change with !limits tracks <n|off> or !limits length <minutes|off>"
+        ))
+        .await?;
+        return Ok(());
+    };
+
+    let value = match value.as_str() {
+        "off" | "none" => None,
+        value => Some(
+            value
+                .parse::<u64>()
+                .map_err(|_| "Limit must be a number or off")?,
+        ),
+    };
+    match kind.as_str() {
+        "tracks" => ctx.data.settings.update(guild_id, |settings| {
+            settings.max_tracks_per_user = value.map(|limit| limit as usize)
+        })?,
+        "length" => ctx.data.settings.update(guild_id, |settings| {
+            settings.max_track_length_secs = value.map(|minutes| minutes * 60)
+        })?,
+        _ => {
+            ctx.say("Unknown limit, use tracks or length").await?;
+            return Ok(());
+        }
+    };
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn fair(ctx: Context<'_>, enabled: Option<String>) -> Result<(), Error> {
+    info!("FAIR invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let enabled = match enabled.as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            let state = if ctx.data.settings.get(guild_id).fair_queue {
+                "on"
+            } else {
+                "off"
+            };
+            ctx.say(format!("Fair queueing is {state}, use !fair on|off"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.fair_queue = enabled)?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn history(ctx: Context<'_>) -> Result<(), Error> {
+    info!("HISTORY invoked by {:?}", &ctx.author().name);
+
+    let entries = ctx.data.driver.history(10);
+    if entries.is_empty() {
+        ctx.say("Nothing has played yet").await?;
+        return Ok(());
+    }
+
+    let mut message = String::from("**Recently played:**\n");
+    for (num, entry) in entries.iter().enumerate() {
+        let started = entry
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        message.push_str(&format!("{}. {}", num + 1, entry.track.display_title()));
+        if let Some(requester) = entry.track.requester {
+            message.push_str(&format!(" by {}", requester.mention()));
+        }
+        message.push_str(&format!(" <t:{started}:R>"));
+        if entry.skipped {
+            message.push_str(" (skipped)");
+        }
+        message.push('\n');
+    }
+
+    // Listing requesters shouldn't ping them
+    ctx.send(
+        CreateReply::default()
+            .content(message)
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn back(ctx: Context<'_>) -> Result<(), Error> {
+    info!("BACK invoked by {:?}", &ctx.author().name);
+
+    let track = ctx.data.driver.back(ctx.author().id)?;
+    ctx.say(format!("Queued {} again", track.display_title()))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn replay(ctx: Context<'_>) -> Result<(), Error> {
+    info!("REPLAY invoked by {:?}", &ctx.author().name);
+
+    ctx.data.driver.replay()?;
+    ctx.msg.react(&ctx.http(), '🔁').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn autoplay(ctx: Context<'_>, enabled: Option<String>) -> Result<(), Error> {
+    info!("AUTOPLAY invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let enabled = match enabled.as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            let state = if ctx.data.settings.get(guild_id).autoplay {
+                "on"
+            } else {
+                "off"
+            };
+            ctx.say(format!("Autoplay is {state}, use !autoplay on|off"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.autoplay = enabled)?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    aliases("np"),
+    check = "permissions::check"
+)]
+pub async fn nowplaying(ctx: Context<'_>) -> Result<(), Error> {
+    info!("NOWPLAYING invoked by {:?}", &ctx.author().name);
+
+    let mut message = match ctx.data.driver.now_playing().await {
+        Some((track, position)) => {
+            let length = track.duration.map_or("?".to_string(), |duration| {
+                format_duration(duration.as_secs())
+            });
+            format!(
+                "**Now playing:** {} [{}/{}]\n",
+                track.display_title(),
+                format_duration(position.as_secs()),
+                length
+            )
+        }
+        None => "Nothing is playing\n".to_string(),
+    };
+
+    if let Some(effect) = ctx.data.driver.effect() {
+        message.push_str(&format!("**Effect:** {effect}\n"));
+    }
+
+    let (upcoming, total) = ctx.data.driver.upcoming(10);
+    for (num, track) in upcoming.iter().enumerate() {
+        message.push_str(&format!("{}. {}", num + 1, track.display_title()));
+        if track.autoplay {
+            message.push_str(" (autoplay)");
+        } else if let Some(requester) = track.requester {
+            message.push_str(&format!(" by {}", requester.mention()));
+        }
+        message.push('\n');
+    }
+    if total > upcoming.len() {
+        message.push_str(&format!("...and {} more", total - upcoming.len()));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(message)
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Splits an optional leading `guild` or `me` keyword off playlist arguments.
+fn scope_keyword<'a>(ctx: &Context<'_>, args: &'a str) -> (Option<Scope>, &'a str) {
+    let args = args.trim();
+    let (first, rest) = args.split_once(' ').unwrap_or((args, ""));
+    match first {
+        "guild" | "server" => (Some(Scope::Guild(ctx.guild_id().unwrap())), rest.trim()),
+        "me" | "mine" => (Some(Scope::User(ctx.author().id)), rest.trim()),
+        _ => (None, args),
+    }
+}
+
+/// Scope of a playlist being changed, the author's own unless they say `guild`.
+fn playlist_scope<'a>(ctx: &Context<'_>, args: &'a str) -> (Scope, &'a str) {
+    let (scope, rest) = scope_keyword(ctx, args);
+    (scope.unwrap_or(Scope::User(ctx.author().id)), rest)
+}
+
+/// Looks a playlist up in `scope`, or the author's and then the guild's
+/// when no scope was given.
+fn find_playlist(
+    ctx: &Context<'_>,
+    scope: Option<Scope>,
+    name: &str,
+) -> Result<Option<Playlist>, Error> {
+    match scope {
+        Some(scope) => ctx.data.playlists.get(scope, name),
+        None => Ok(ctx
+            .data
+            .playlists
+            .find(ctx.author().id, ctx.guild_id().unwrap(), name)?
+            .map(|(_, playlist)| playlist)),
+    }
+}
+
+/// Playlist names are a single word, so they can lead the arguments.
+fn is_playlist_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(char::is_whitespace)
+}
+
+/// Personal playlists belong to their owner, guild ones to DJs and their creator.
+async fn can_edit_playlist(ctx: Context<'_>, scope: Scope, name: &str) -> Result<bool, Error> {
+    let Scope::Guild(_) = scope else {
+        return Ok(true);
+    };
+    if permissions::author_access(poise::Context::Prefix(ctx)).await >= Access::Dj {
+        return Ok(true);
+    }
+    let owner = ctx.data.playlists.get(scope, name)?.map(|p| p.owner);
+    Ok(owner.is_none_or(|owner| owner == ctx.author().id))
+}
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    aliases("pl"),
+    check = "permissions::check",
+    subcommands(
+        "playlist_save",
+        "playlist_load",
+        "playlist_add",
+        "playlist_remove",
+        "playlist_list",
+        "playlist_delete"
+    )
+)]
+pub async fn playlist(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(
+        "Use !playlist save|add|remove|delete [guild] <name>, !playlist load [guild|me] <name> or !playlist list [guild|me] [name]",
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "save", check = "permissions::check")]
+pub async fn playlist_save(ctx: Context<'_>, #[rest] args: String) -> Result<(), Error> {
+    info!("PLAYLIST SAVE invoked by {:?}", &ctx.author().name);
+
+    let (scope, name) = playlist_scope(&ctx, &args);
+    if !is_playlist_name(name) {
+        ctx.say("Playlist names are a single word").await?;
+        return Ok(());
+    }
+    if !can_edit_playlist(ctx, scope, name).await? {
+        ctx.say("Only DJs and its creator can replace that playlist")
+            .await?;
+        return Ok(());
+    }
+
+    let tracks = ctx.data.driver.queued_tracks();
+    if tracks.is_empty() {
+        ctx.say("There is nothing queued to save").await?;
+        return Ok(());
+    }
+    let count = tracks.len();
+    ctx.data.playlists.save(
+        scope,
+        Playlist {
+            name: name.to_string(),
+            owner: ctx.author().id,
+            tracks,
+        },
+    )?;
+    ctx.say(format!("Saved {count} tracks to {name}")).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "load", check = "permissions::check")]
+pub async fn playlist_load(ctx: Context<'_>, #[rest] args: String) -> Result<(), Error> {
+    info!("PLAYLIST LOAD invoked by {:?}", &ctx.author().name);
+
+    let (scope, name) = scope_keyword(&ctx, &args);
+    if name.is_empty() {
+        ctx.say("Use !playlist load [guild|me] <name>").await?;
+        return Ok(());
+    }
+    let Some(playlist) = find_playlist(&ctx, scope, name)? else {
+        ctx.say(format!("There is no playlist named {name}"))
+            .await?;
+        return Ok(());
+    };
+
+    let total = playlist.tracks.len();
+    let mut queued = 0;
+    for mut track in playlist.tracks {
+        track.requester = Some(ctx.author().id);
+        track.autoplay = false;
+        if let Err(e) = ctx.data.driver.enqueue_input(track).await {
+            ctx.say(format!("Queued {queued} of {total} tracks: {e}"))
+                .await?;
+            return Ok(());
+        }
+        queued += 1;
+    }
+    ctx.say(format!("Queued {queued} tracks from {}", playlist.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "add", check = "permissions::check")]
+pub async fn playlist_add(ctx: Context<'_>, #[rest] args: String) -> Result<(), Error> {
+    info!("PLAYLIST ADD invoked by {:?}", &ctx.author().name);
+
+    let (scope, args) = playlist_scope(&ctx, &args);
+    let Some((name, input)) = args.split_once(' ') else {
+        ctx.say("Use !playlist add [guild] <name> <link or search>")
+            .await?;
+        return Ok(());
+    };
+    if !is_playlist_name(name) {
+        ctx.say("Playlist names are a single word").await?;
+        return Ok(());
+    }
+    if !can_edit_playlist(ctx, scope, name).await? {
+        ctx.say("Only DJs and its creator can change that playlist")
+            .await?;
+        return Ok(());
+    }
+
+    let track = ctx
+        .data
+        .resolve_track(input.trim().to_string(), ctx.author().id)
+        .await?;
+    let title = track.display_title().to_string();
+    let count = ctx
+        .data
+        .playlists
+        .add(scope, name, ctx.author().id, track)?;
+    ctx.say(format!("Added {title} to {name} ({count} tracks)"))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "remove", check = "permissions::check")]
+pub async fn playlist_remove(ctx: Context<'_>, #[rest] args: String) -> Result<(), Error> {
+    info!("PLAYLIST REMOVE invoked by {:?}", &ctx.author().name);
+
+    let (scope, args) = playlist_scope(&ctx, &args);
+    let Some((name, Ok(index))) = args
+        .split_once(' ')
+        .map(|(name, index)| (name, index.trim().parse::<usize>()))
+    else {
+        ctx.say("Use !playlist remove [guild] <name> <number>")
+            .await?;
+        return Ok(());
+    };
+    if index == 0 {
+        ctx.say("Track numbers start at 1").await?;
+        return Ok(());
+    }
+    if !can_edit_playlist(ctx, scope, name).await? {
+        ctx.say("Only DJs and its creator can change that playlist")
+            .await?;
+        return Ok(());
+    }
+
+    let track = ctx.data.playlists.remove(scope, name, index - 1)?;
+    ctx.say(format!("Removed {} from {name}", track.display_title()))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "list", check = "permissions::check")]
+pub async fn playlist_list(ctx: Context<'_>, #[rest] args: Option<String>) -> Result<(), Error> {
+    info!("PLAYLIST LIST invoked by {:?}", &ctx.author().name);
+
+    let args = args.unwrap_or_default();
+    let (scope, name) = scope_keyword(&ctx, &args);
+    if name.is_empty() {
+        let scopes = [
+            ("Your playlists", Scope::User(ctx.author().id)),
+            ("Server playlists", Scope::Guild(ctx.guild_id().unwrap())),
+        ];
+        let mut message = String::new();
+        for (label, scope) in scopes
+            .into_iter()
+            .filter(|(_, listed)| scope.is_none_or(|scope| scope == *listed))
+        {
+            let playlists = ctx.data.playlists.list(scope)?;
+            message.push_str(&format!("**{label}:**"));
+            if playlists.is_empty() {
+                message.push_str(" none");
+            }
+            for playlist in playlists {
+                message.push_str(&format!(
+                    "\n- {} ({} tracks)",
+                    playlist.name,
+                    playlist.tracks.len()
+                ));
+            }
+            message.push('\n');
+        }
+        ctx.say(message).await?;
+        return Ok(());
+    }
+
+    let Some(playlist) = find_playlist(&ctx, scope, name)? else {
+        ctx.say(format!("There is no playlist named {name}"))
+            .await?;
+        return Ok(());
+    };
+    let mut message = format!("**{}** ({} tracks)\n", playlist.name, playlist.tracks.len());
+    for (num, track) in playlist.tracks.iter().enumerate().take(25) {
+        message.push_str(&format!("{}. {}\n", num + 1, track.display_title()));
+    }
+    if playlist.tracks.len() > 25 {
+        message.push_str(&format!("...and {} more", playlist.tracks.len() - 25));
+    }
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "delete", check = "permissions::check")]
+pub async fn playlist_delete(ctx: Context<'_>, #[rest] args: String) -> Result<(), Error> {
+    info!("PLAYLIST DELETE invoked by {:?}", &ctx.author().name);
+
+    let (scope, name) = playlist_scope(&ctx, &args);
+    if !can_edit_playlist(ctx, scope, name).await? {
+        ctx.say("Only DJs and its creator can delete that playlist")
+            .await?;
+        return Ok(());
+    }
+
+    match ctx.data.playlists.delete(scope, name)? {
+        Some(playlist) => ctx.say(format!("Deleted {}", playlist.name)).await?,
+        None => {
+            ctx.say(format!("There is no playlist named {name}"))
+                .await?
+        }
+    };
+    Ok(())
+}
+
+/// Most entries a single import will try to resolve.
+const IMPORT_LIMIT: usize = 100;
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn export(ctx: Context<'_>, #[rest] args: Option<String>) -> Result<(), Error> {
+    info!("EXPORT invoked by {:?}", &ctx.author().name);
+
+    let args = args.unwrap_or_default();
+    let mut words = args.split_whitespace().peekable();
+    let format = match words.peek().map(|word| word.parse::<Format>()) {
+        Some(Ok(format)) => {
+            words.next();
+            format
+        }
+        _ => Format::M3u,
+    };
+
+    let rest = words.collect::<Vec<_>>().join(" ");
+    let (scope, name) = scope_keyword(&ctx, &rest);
+    let (name, tracks) = if name.is_empty() {
+        ("queue".to_string(), ctx.data.driver.queued_tracks())
+    } else {
+        match find_playlist(&ctx, scope, name)? {
+            Some(playlist) => (playlist.name, playlist.tracks),
+            None => {
+                ctx.say(format!("There is no playlist named {name}"))
+                    .await?;
+                return Ok(());
+            }
+        }
+    };
+    if tracks.is_empty() {
+        ctx.say("There is nothing to export").await?;
+        return Ok(());
+    }
+
+    let content = formats::export(format, &name, &tracks)?;
+    let filename = formats::filename(format, &name);
+    ctx.send(
+        CreateReply::default()
+            .content(format!("{} tracks", tracks.len()))
+            .attachment(CreateAttachment::bytes(content.into_bytes(), filename)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn import(ctx: Context<'_>, format: Option<String>) -> Result<(), Error> {
+    info!("IMPORT invoked by {:?}", &ctx.author().name);
+
+    let Some(attachment) = ctx.msg.attachments.first() else {
+        ctx.say("Attach an .m3u8, .xspf or .json playlist to import")
+            .await?;
+        return Ok(());
+    };
+    let format = match format {
+        Some(format) => format.parse::<Format>()?,
+        None => Format::from_filename(&attachment.filename)
+            .ok_or("Can't tell the playlist format from the file name, pass m3u8, xspf or json")?,
+    };
+
+    let content = String::from_utf8(attachment.download().await?)?;
+    let entries = formats::import(format, &content)?;
+    if entries.is_empty() {
+        ctx.say("That playlist has no tracks in it").await?;
+        return Ok(());
+    }
+
+    let total = entries.len().min(IMPORT_LIMIT);
+    let mut queued = 0;
+    let mut failed = 0;
+    for entry in entries.into_iter().take(IMPORT_LIMIT) {
+        let track = match ctx.data.resolve_track(entry.clone(), ctx.author().id).await {
+            Ok(track) => track,
+            Err(e) => {
+                warn!("Could not resolve imported entry {entry:?}: {e}");
+                failed += 1;
+                continue;
+            }
+        };
+        if let Err(e) = ctx.data.driver.enqueue_input(track).await {
+            ctx.say(format!("Queued {queued} of {total} tracks: {e}"))
+                .await?;
+            return Ok(());
+        }
+        queued += 1;
+    }
+
+    let mut message = format!("Queued {queued} of {total} tracks");
+    if failed > 0 {
+        message.push_str(&format!(", {failed} could not be found"));
+    }
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn normalize(ctx: Context<'_>, setting: Option<String>) -> Result<(), Error> {
+    info!("NORMALIZE invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    match setting.as_deref() {
+        Some("on") => ctx
+            .data
+            .settings
+            .update(guild_id, |settings| settings.normalize = true)?,
+        Some("off") => ctx
+            .data
+            .settings
+            .update(guild_id, |settings| settings.normalize = false)?,
+        Some(target) => {
+            let target = target
+                .parse::<f32>()
+                .ok()
+                .filter(|target| (-40.0..=-5.0).contains(target))
+                .ok_or("Target must be on, off or a loudness between -40 and -5 LUFS")?;
+            ctx.data.settings.update(guild_id, |settings| {
+                settings.normalize = true;
+                settings.target_lufs = target;
+            })?
+        }
+        None => {
+            let settings = ctx.data.settings.get(guild_id);
+            let state = if settings.normalize { "on" } else { "off" };
+            ctx.say(format!(
+                "Normalization is {state}, targeting {} LUFS. Use !normalize on|off|<target>",
+                settings.target_lufs
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
     ctx.msg.react(&ctx.http(), '✅').await?;
     Ok(())
 }
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn fx(ctx: Context<'_>, #[rest] effect: Option<String>) -> Result<(), Error> {
+    info!("FX invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(effect) = effect else {
+        let message = match ctx.data.settings.get(guild_id).effect {
+            Some(effect) => format!("Effect is {effect}, clear it with !fx off"),
+            None => {
+                "No effect. Use !fx bassboost|nightcore|vaporwave|eq <bands>|speed <x>".to_string()
+            }
+        };
+        ctx.say(message).await?;
+        return Ok(());
+    };
+
+    let effect = match effect.trim() {
+        "off" | "none" | "clear" => None,
+        effect => Some(effect.parse::<Effect>()?),
+    };
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.effect = effect.clone())?;
+    ctx.data.driver.set_effect(guild_id, effect);
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn crossfade(ctx: Context<'_>, seconds: Option<u64>) -> Result<(), Error> {
+    info!("CROSSFADE invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(seconds) = seconds else {
+        let seconds = ctx.data.settings.get(guild_id).crossfade_secs;
+        ctx.say(format!(
+            "Crossfade is {seconds}s, use !crossfade <0-{MAX_CROSSFADE_SECS}> to change it"
+        ))
+        .await?;
+        return Ok(());
+    };
+    if seconds > MAX_CROSSFADE_SECS {
+        return Err(format!("Crossfade can be at most {MAX_CROSSFADE_SECS} seconds").into());
+    }
+
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.crossfade_secs = seconds)?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+#[poise::command(prefix_command, owners_only, subcommands("cache_stats", "cache_purge"))]
+pub async fn cache(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use !cache stats or !cache purge").await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "stats", owners_only)]
+pub async fn cache_stats(ctx: Context<'_>) -> Result<(), Error> {
+    info!("CACHE STATS invoked by {:?}", &ctx.author().name);
+
+    let stats = ctx.data.cache.stats()?;
+    let lookups = stats.hits + stats.misses;
+    let hit_rate = match lookups {
+        0 => 0.0,
+        _ => stats.hits as f64 * 100.0 / lookups as f64,
+    };
+    ctx.say(format!(
+        "{} tracks cached, {:.1} of {:.0} MiB used\n{} hits and {} misses since startup ({hit_rate:.0}%)",
+        stats.entries,
+        stats.size as f64 / MIB,
+        stats.max_size as f64 / MIB,
+        stats.hits,
+        stats.misses
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "purge", owners_only)]
+pub async fn cache_purge(ctx: Context<'_>) -> Result<(), Error> {
+    info!("CACHE PURGE invoked by {:?}", &ctx.author().name);
+
+    let (entries, size) = ctx.data.cache.purge()?;
+    ctx.say(format!(
+        "Removed {entries} cached tracks, {:.1} MiB freed",
+        size as f64 / MIB
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    check = "permissions::check",
+    subcommands("sb_list", "sb_panel", "sb_add", "sb_remove", "sb_volume")
+)]
+pub async fn sb(ctx: Context<'_>, name: Option<String>) -> Result<(), Error> {
+    info!("SB invoked by {:?}", &ctx.author().name);
+
+    let Some(name) = name else {
+        ctx.say("Use !sb <clip>, !sb list or !sb panel. Admins can !sb add <name> with an attachment, !sb remove <name> and !sb volume <name> <0-200>")
+            .await?;
+        return Ok(());
+    };
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird voice client err")
+        .clone();
+
+    ctx.data
+        .play_clip(manager, ctx.guild_id().unwrap(), ctx.author().id, &name)
+        .await?;
+    ctx.msg.react(&ctx.http(), '🔊').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "list", check = "permissions::check")]
+pub async fn sb_list(ctx: Context<'_>) -> Result<(), Error> {
+    info!("SB LIST invoked by {:?}", &ctx.author().name);
+
+    let clips = ctx.data.soundboard.list(ctx.guild_id().unwrap());
+    if clips.is_empty() {
+        ctx.say("The soundboard is empty").await?;
+    } else {
+        ctx.say(format!("**Clips:** {}", clips.join(", "))).await?;
+    }
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "panel", check = "permissions::check")]
+pub async fn sb_panel(ctx: Context<'_>) -> Result<(), Error> {
+    info!("SB PANEL invoked by {:?}", &ctx.author().name);
+
+    let panel = ctx.data.soundboard.panel(ctx.guild_id().unwrap());
+    if panel.is_empty() {
+        ctx.say("The soundboard is empty, admins can add clips with !sb add <name>")
+            .await?;
+        return Ok(());
+    }
+    ctx.send(
+        CreateReply::default()
+            .content("**Soundboard**")
+            .components(panel),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "add", check = "permissions::check")]
+pub async fn sb_add(ctx: Context<'_>, name: String) -> Result<(), Error> {
+    info!("SB ADD invoked by {:?}", &ctx.author().name);
+
+    require_admin(ctx, "manage the soundboard").await?;
+    let Some(attachment) = ctx.msg.attachments.first() else {
+        ctx.say(format!(
+            "Attach the clip to add, one of {}",
+            soundboard::EXTENSIONS.join(", ")
+        ))
+        .await?;
+        return Ok(());
+    };
+    if attachment.size as usize > soundboard::MAX_CLIP_BYTES {
+        return Err(format!(
+            "Clips can be at most {} KiB",
+            soundboard::MAX_CLIP_BYTES / 1024
+        )
+        .into());
+    }
+
+    let extension = attachment
+        .filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    let content = attachment.download().await?;
+    ctx.data.soundboard.save(
+        ctx.guild_id().unwrap(),
+        &name.to_lowercase(),
+        &extension,
+        &content,
+    )?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "remove", check = "permissions::check")]
+pub async fn sb_remove(ctx: Context<'_>, name: String) -> Result<(), Error> {
+    info!("SB REMOVE invoked by {:?}", &ctx.author().name);
+
+    require_admin(ctx, "manage the soundboard").await?;
+    ctx.data
+        .soundboard
+        .remove(ctx.guild_id().unwrap(), &name.to_lowercase())?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "volume", check = "permissions::check")]
+pub async fn sb_volume(ctx: Context<'_>, name: String, percent: u32) -> Result<(), Error> {
+    info!("SB VOLUME invoked by {:?}", &ctx.author().name);
+
+    require_admin(ctx, "manage the soundboard").await?;
+    if percent > 200 {
+        return Err("Clip volume goes up to 200%".into());
+    }
+    ctx.data.soundboard.set_volume(
+        ctx.guild_id().unwrap(),
+        &name.to_lowercase(),
+        percent as f32 / 100.0,
+    )?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+/// For subcommands that need more than their parent's access level.
+async fn require_admin(ctx: Context<'_>, action: &str) -> Result<(), Error> {
+    if permissions::author_access(poise::Context::Prefix(ctx)).await >= Access::Admin {
+        Ok(())
+    } else {
+        Err(format!("You need admin access to {action}").into())
+    }
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn say(ctx: Context<'_>, #[rest] text: Option<String>) -> Result<(), Error> {
+    info!("SAY invoked by {:?}", &ctx.author().name);
+
+    let Some(text) = text.filter(|text| !text.trim().is_empty()) else {
+        ctx.say("Use !say <text>").await?;
+        return Ok(());
+    };
+    if text.chars().count() > MAX_SPEECH_CHARS {
+        return Err(format!("Keep it under {MAX_SPEECH_CHARS} characters").into());
+    }
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird voice client err")
+        .clone();
+
+    ctx.data.driver.say(manager, &text).await?;
+    ctx.msg.react(&ctx.http(), '🗣').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn announce(ctx: Context<'_>, enabled: Option<String>) -> Result<(), Error> {
+    info!("ANNOUNCE invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    let enabled = match enabled.as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            let state = if ctx.data.settings.get(guild_id).announce {
+                "on"
+            } else {
+                "off"
+            };
+            ctx.say(format!(
+                "Track announcements are {state}, use !announce on|off"
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.announce = enabled)?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+/// Recordings up to this size are attached, larger ones stay on disk. The
+/// WAV files are uncompressed, so that is about 87 seconds of audio shared
+/// between the mix and every speaker.
+const MAX_ATTACHED_BYTES: u64 = 8 * 1024 * 1024;
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    check = "permissions::check",
+    subcommands("record_start", "record_stop", "record_enable", "record_disable")
+)]
+pub async fn record(ctx: Context<'_>) -> Result<(), Error> {
+    let allowed = ctx
+        .data
+        .settings
+        .get(ctx.guild_id().unwrap())
+        .recording_allowed;
+    let state = if allowed { "allowed" } else { "disabled" };
+    ctx.say(format!(
+        "Recording is {state} here. Use !record start or !record stop, bot owners can !record enable|disable"
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "start", check = "permissions::check")]
+pub async fn record_start(ctx: Context<'_>) -> Result<(), Error> {
+    info!("RECORD START invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    if !ctx.data.settings.get(guild_id).recording_allowed {
+        return Err("Recording is disabled here, a bot owner has to !record enable it".into());
+    }
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird voice client err")
+        .clone();
+    let call = match ctx.data.driver.guild() {
+        Some(connected) if connected == guild_id => manager.get(guild_id),
+        _ => None,
+    }
+    .ok_or("Not connected in a voice channel, use !join to connect")?;
+    let channel_id = ctx.data.driver.channel().unwrap();
+
+    let handler = ctx.data.recorder.start(guild_id, channel_id)?;
+    let generation = handler.generation;
+    {
+        let mut call = call.lock().await;
+        call.add_global_event(CoreEvent::SpeakingStateUpdate.into(), handler.clone());
+        call.add_global_event(CoreEvent::VoiceTick.into(), handler);
+    }
+
+    // Everyone in the channel gets told, in text and out loud
+    ctx.say(format!(
+        "🔴 **Recording** {} for up to {} minutes, started by {}. Use !record stop to end it\n\
+        Only recordings under {} MiB are posted here, about {} seconds counting the mix and every speaker",
+        channel_id.mention(),
+        MAX_RECORDING.as_secs() / 60,
+        ctx.author().mention(),
+        MAX_ATTACHED_BYTES / 1024 / 1024,
+        MAX_ATTACHED_BYTES / BYTES_PER_SECOND
+    ))
+    .await?;
+    if let Err(e) = ctx
+        .data
+        .driver
+        .say(manager, "This channel is now being recorded")
+        .await
+    {
+        warn!("Could not announce the recording: {e}");
+    }
+
+    let recorder = ctx.data.recorder.clone();
+    let http = ctx.serenity_context().http.clone();
+    let text_channel = ctx.channel_id();
+    tokio::spawn(async move {
+        tokio::time::sleep(MAX_RECORDING).await;
+        let result = match recorder.stop_if_expired(generation) {
+            Some(Ok(finished)) => post_recording(&http, text_channel, finished).await,
+            Some(Err(e)) => Err(e),
+            None => return,
+        };
+        if let Err(e) = result {
+            warn!("Could not finish the recording: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "stop", check = "permissions::check")]
+pub async fn record_stop(ctx: Context<'_>) -> Result<(), Error> {
+    info!("RECORD STOP invoked by {:?}", &ctx.author().name);
+
+    let finished = ctx.data.recorder.stop(ctx.guild_id().unwrap())?;
+    post_recording(&ctx.serenity_context().http, ctx.channel_id(), finished).await
+}
+
+#[poise::command(prefix_command, rename = "enable", owners_only)]
+pub async fn record_enable(ctx: Context<'_>) -> Result<(), Error> {
+    info!("RECORD ENABLE invoked by {:?}", &ctx.author().name);
+
+    ctx.data
+        .settings
+        .update(ctx.guild_id().unwrap(), |settings| {
+            settings.recording_allowed = true
+        })?;
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "disable", owners_only)]
+pub async fn record_disable(ctx: Context<'_>) -> Result<(), Error> {
+    info!("RECORD DISABLE invoked by {:?}", &ctx.author().name);
+
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data
+        .settings
+        .update(guild_id, |settings| settings.recording_allowed = false)?;
+    // A running recording does not outlive the permission
+    if ctx.data.recorder.is_recording(guild_id) {
+        let finished = ctx.data.recorder.stop(guild_id)?;
+        post_recording(&ctx.serenity_context().http, ctx.channel_id(), finished).await?;
+    }
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+/// Posts a finished recording, attaching the files when they fit.
+async fn post_recording(
+    http: &Http,
+    channel_id: ChannelId,
+    finished: FinishedRecording,
+) -> Result<(), Error> {
+    let speakers = finished
+        .files
+        .iter()
+        .filter_map(|file| file.user.map(|user| user.mention().to_string()))
+        .collect::<Vec<_>>();
+    let mut content = format!(
+        "⏹ Recorded {} for {}",
+        finished.channel_id.mention(),
+        format_duration(finished.length.as_secs())
+    );
+    if !speakers.is_empty() {
+        content.push_str(&format!(", speakers: {}", speakers.join(", ")));
+    }
+
+    let total: u64 = finished.files.iter().map(|file| file.size).sum();
+    let mut message = CreateMessage::new().allowed_mentions(CreateAllowedMentions::new());
+    if total <= MAX_ATTACHED_BYTES {
+        for file in &finished.files {
+            message = message.add_file(CreateAttachment::path(&file.path).await?);
+        }
+    } else {
+        content.push_str(&format!(
+            "\nToo large to attach ({:.1} MiB, the limit is {} MiB), saved to `{}`",
+            total as f64 / MIB,
+            MAX_ATTACHED_BYTES / 1024 / 1024,
+            finished.dir.display()
+        ));
+    }
+    channel_id
+        .send_message(http, message.content(content))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    guild_only,
+    check = "permissions::check",
+    subcommands("quiz_stop")
+)]
+pub async fn quiz(ctx: Context<'_>, #[rest] args: Option<String>) -> Result<(), Error> {
+    info!("QUIZ invoked by {:?}", &ctx.author().name);
+
+    // !quiz [rounds] [playlist]
+    let args = args.unwrap_or_default();
+    let mut words = args.split_whitespace().peekable();
+    let rounds = match words.peek().and_then(|word| word.parse::<usize>().ok()) {
+        Some(rounds) => {
+            words.next();
+            rounds
+        }
+        None => DEFAULT_ROUNDS,
+    };
+    if rounds == 0 || rounds > MAX_ROUNDS {
+        return Err(format!("A quiz has 1 to {MAX_ROUNDS} rounds").into());
+    }
+
+    // Tracks from a playlist, or whatever played here recently
+    let tracks = match words.next() {
+        Some(name) => {
+            ctx.data
+                .playlists
+                .find(ctx.author().id, ctx.guild_id().unwrap(), name)?
+                .ok_or(format!("There is no playlist named {name}"))?
+                .1
+                .tracks
+        }
+        None => {
+            let mut seen = HashSet::new();
+            ctx.data
+                .driver
+                .history(50)
+                .into_iter()
+                .map(|entry| entry.track)
+                .filter(|track| seen.insert(track_key(&track.url)))
+                .collect()
+        }
+    };
+    if tracks.is_empty() {
+        ctx.say("Nothing to quiz on, use !quiz [rounds] <playlist> or play some music first")
+            .await?;
+        return Ok(());
+    }
+    let rounds = rounds.min(tracks.len());
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird voice client err")
+        .clone();
+
+    let stop = ctx.data.quiz.begin()?;
+    ctx.say(format!(
+        "🎶 **Music quiz**, {rounds} rounds of {} second snippets. Type your guesses here!",
+        quiz::SNIPPET_LENGTH.as_secs()
+    ))
+    .await?;
+    let result = quiz::run(ctx, manager, tracks, rounds, stop).await;
+    ctx.data.quiz.end();
+    result
+}
+
+#[poise::command(prefix_command, rename = "stop", check = "permissions::check")]
+pub async fn quiz_stop(ctx: Context<'_>) -> Result<(), Error> {
+    info!("QUIZ STOP invoked by {:?}", &ctx.author().name);
+
+    if !ctx.data.quiz.stop() {
+        return Err("There is no quiz running".into());
+    }
+    ctx.msg.react(&ctx.http(), '✅').await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, check = "permissions::check")]
+pub async fn lyrics(ctx: Context<'_>) -> Result<(), Error> {
+    info!("LYRICS invoked by {:?}", &ctx.author().name);
+
+    let Some((track, _)) = ctx.data.driver.now_playing().await else {
+        ctx.say("There is nothing playing").await?;
+        return Ok(());
+    };
+    match lyrics::find(ctx.data.lyrics.as_ref(), &track).await? {
+        Lyrics::Synced(lines) => lyrics::follow(ctx, &track, &lines).await?,
+        Lyrics::Plain(text) => {
+            let pages = text::pages(&text, text::PAGE_CHARS);
+            let pages = pages.iter().map(String::as_str).collect::<Vec<_>>();
+            poise::builtins::paginate(poise::Context::Prefix(ctx), &pages).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Quest names matching what has been typed so far, best first.
+async fn autocomplete_quest(ctx: poise::Context<'_, Bot, Error>, partial: &str) -> Vec<String> {
+    let Ok(quests) = ctx.data().tarkov.quests() else {
+        return Vec::new();
+    };
+    search::rank(&quests, partial, |quest| [quest.name.as_str()])
+        .into_iter()
+        .take(MAX_MENU_CHOICES)
+        .map(|found| found.item.name.clone())
+        .collect()
+}
+
+/// Looks a quest up by name
+#[poise::command(
+    prefix_command,
+    slash_command,
+    check = "permissions::check",
+    subcommands(
+        "quest_find",
+        "quest_done",
+        "quest_undo",
+        "quest_next",
+        "quest_level",
+        "quest_trader"
+    )
+)]
+pub async fn quest(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Quest name"]
+    #[autocomplete = "autocomplete_quest"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    info!("QUEST invoked by {:?}", &ctx.author().name);
+    show_quest(ctx, &name).await
+}
+
+/// Looks a quest up by name
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "find",
+    check = "permissions::check"
+)]
+pub async fn quest_find(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Quest name"]
+    #[autocomplete = "autocomplete_quest"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    info!("QUEST FIND invoked by {:?}", &ctx.author().name);
+    show_quest(ctx, &name).await
+}
+
+/// Replies with the quest `name` matches best, or a menu when several do.
+async fn show_quest(ctx: poise::Context<'_, Bot, Error>, name: &str) -> Result<(), Error> {
+    // Fetching the quest can outlast the three seconds a slash command gets
+    ctx.defer().await?;
+    let quests = ctx.data().tarkov.quests()?;
+    let matches = search::rank(&quests, name, |quest| [quest.name.as_str()]);
+    if matches.is_empty() {
+        ctx.say("No quests found with that name").await?;
+        return Ok(());
+    }
+
+    if let Some(found) = search::best(&matches) {
+        let cached = ctx.data().tarkov.task(&found.item.id).await?;
+        let task =
+            Option::as_ref(&cached.value).ok_or("tarkov.dev does not know that quest anymore")?;
+        let mut reply = CreateReply::default().embed(task_embed(task));
+        if let Some(note) = cached.note() {
+            reply = reply.content(note);
+        }
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    let content = if matches.len() > MAX_MENU_CHOICES {
+        format!(
+            "{} quests match, showing the closest {MAX_MENU_CHOICES}. Be more specific to narrow it down",
+            matches.len()
+        )
+    } else {
+        format!("{} quests match, pick one", matches.len())
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(vec![quest_menu(&matches)]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// The quest `name` clearly refers to, suggesting a few when it is ambiguous.
+fn resolve_quest(quests: &[Quest], name: &str) -> Result<Quest, Error> {
+    let matches = search::rank(quests, name, |quest| [quest.name.as_str()]);
+    if let Some(found) = search::best(&matches) {
+        return Ok(found.item.clone());
+    }
+    if matches.is_empty() {
+        return Err("No quests found with that name".into());
+    }
+    let closest = matches
+        .iter()
+        .take(5)
+        .map(|found| found.item.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(format!("Which quest? The closest are {closest}").into())
+}
+
+/// Marks a quest and everything before it as completed
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "done",
+    check = "permissions::check"
+)]
+pub async fn quest_done(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Quest name"]
+    #[autocomplete = "autocomplete_quest"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    info!("QUEST DONE invoked by {:?}", &ctx.author().name);
+
+    let quest = resolve_quest(&ctx.data().tarkov.quests()?, &name)?;
+    ctx.defer().await?;
+    let tree = ctx.data().tarkov.quest_tree().await?;
+    let completed = progress::with_prerequisites(&tree.value, &quest.id);
+    let (newly_done, prerequisites) =
+        ctx.data()
+            .quest_progress
+            .update(ctx.author().id, |progress| {
+                let newly_done = progress.done.insert(quest.id.clone());
+                let before = progress.done.len();
+                progress.done.extend(completed);
+                (newly_done, progress.done.len() - before)
+            })?;
+    let content = match (newly_done, prerequisites) {
+        (false, 0) => format!("**{}** was already done", quest.name),
+        (true, 0) => format!("Marked **{}** done", quest.name),
+        (true, before) => format!(
+            "Marked **{}** done, with {before} quests before it",
+            quest.name
+        ),
+        (false, before) => format!(
+            "**{}** was already done, marked {before} quests before it done too",
+            quest.name
+        ),
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+/// Marks a quest as not completed again
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "undo",
+    check = "permissions::check"
+)]
+pub async fn quest_undo(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Quest name"]
+    #[autocomplete = "autocomplete_quest"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    info!("QUEST UNDO invoked by {:?}", &ctx.author().name);
+
+    let quest = resolve_quest(&ctx.data().tarkov.quests()?, &name)?;
+    let removed = ctx
+        .data()
+        .quest_progress
+        .update(ctx.author().id, |progress| progress.done.remove(&quest.id))?;
+    let content = if removed {
+        format!("**{}** is no longer done", quest.name)
+    } else {
+        format!("**{}** was not marked done", quest.name)
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+/// Lists the quests you can take now
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "next",
+    check = "permissions::check"
+)]
+pub async fn quest_next(ctx: poise::Context<'_, Bot, Error>) -> Result<(), Error> {
+    info!("QUEST NEXT invoked by {:?}", &ctx.author().name);
+    ctx.defer().await?;
+
+    let progress = ctx.data().quest_progress.get(ctx.author().id)?;
+    let tree = ctx.data().tarkov.quest_tree().await?;
+    let unlocked = progress::unlocked(&tree.value, &progress);
+    if unlocked.is_empty() {
+        ctx.say("No quests are open to you right now").await?;
+        return Ok(());
+    }
+
+    let mut header = format!("📜 **{} quests available**", unlocked.len());
+    if progress.level.is_none() {
+        header.push_str(", set your level with !quest level to filter by it");
+    }
+    if let Some(note) = tree.note() {
+        header = format!("{note}\n{header}");
+    }
+    let list = unlocked
+        .iter()
+        .map(|quest| {
+            let level = quest
+                .min_player_level
+                .map_or(String::new(), |level| format!(", level {level}"));
+            format!("• **{}** ({}{level})", quest.name, quest.trader.name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let pages = text::pages(&list, text::PAGE_CHARS)
+        .into_iter()
+        .map(|page| format!("{header}\n{page}"))
+        .collect::<Vec<_>>();
+    let pages = pages.iter().map(String::as_str).collect::<Vec<_>>();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// Sets your player level for !quest next
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "level",
+    check = "permissions::check"
+)]
+pub async fn quest_level(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Player level"] level: u32,
+) -> Result<(), Error> {
+    info!("QUEST LEVEL invoked by {:?}", &ctx.author().name);
+
+    if !(1..=MAX_PLAYER_LEVEL).contains(&level) {
+        return Err(format!("Levels go from 1 to {MAX_PLAYER_LEVEL}").into());
+    }
+    ctx.data()
+        .quest_progress
+        .update(ctx.author().id, |progress| progress.level = Some(level))?;
+    ctx.say(format!("Your level is now {level}")).await?;
+    Ok(())
+}
+
+/// Sets your loyalty level with a trader for !quest next
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "trader",
+    check = "permissions::check"
+)]
+pub async fn quest_trader(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Trader name"] trader: String,
+    #[description = "Loyalty level"] level: u32,
+) -> Result<(), Error> {
+    info!("QUEST TRADER invoked by {:?}", &ctx.author().name);
+
+    if !(1..=MAX_LOYALTY_LEVEL).contains(&level) {
+        return Err(format!("Loyalty levels go from 1 to {MAX_LOYALTY_LEVEL}").into());
+    }
+    let tree = ctx.data().tarkov.quest_tree().await?;
+    let name = tree
+        .value
+        .iter()
+        .map(|quest| &quest.trader.name)
+        .find(|name| name.eq_ignore_ascii_case(trader.trim()))
+        .ok_or_else(|| format!("No trader called {trader} gives quests"))?
+        .clone();
+    ctx.data()
+        .quest_progress
+        .update(ctx.author().id, |progress| {
+            progress.traders.insert(name.clone(), level)
+        })?;
+    ctx.say(format!("{name} is now at loyalty level {level} for you"))
+        .await?;
+    Ok(())
+}
+
+/// Shows your progress towards the Kappa container
+#[poise::command(prefix_command, slash_command, check = "permissions::check")]
+pub async fn kappa(ctx: poise::Context<'_, Bot, Error>) -> Result<(), Error> {
+    info!("KAPPA invoked by {:?}", &ctx.author().name);
+    ctx.defer().await?;
+
+    let progress = ctx.data().quest_progress.get(ctx.author().id)?;
+    let tree = ctx.data().tarkov.quest_tree().await?;
+    let kappa = progress::kappa(&tree.value, &progress);
+
+    let mut header = format!(
+        "🏆 **Kappa** {} {}/{} quests",
+        progress_bar(kappa.done, kappa.total),
+        kappa.done,
+        kappa.total
+    );
+    if let Some(note) = tree.note() {
+        header = format!("{note}\n{header}");
+    }
+    if kappa.remaining.is_empty() {
+        ctx.say(format!(
+            "{header}\nEvery Kappa quest is done, enjoy the container"
+        ))
+        .await?;
+        return Ok(());
+    }
+    let items = if kappa.items.is_empty() {
+        "No found in raid items left to hand in".to_string()
+    } else {
+        kappa
+            .items
+            .iter()
+            .map(|(name, count)| format!("• {count}x {name}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let text = format!(
+        "**Found in raid items still needed**\n{items}\n\n**Quests left**\n{}",
+        kappa
+            .remaining
+            .iter()
+            .map(|quest| format!("• {}", quest.name))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    let pages = text::pages(&text, text::PAGE_CHARS)
+        .into_iter()
+        .map(|page| format!("{header}\n{page}"))
+        .collect::<Vec<_>>();
+    let pages = pages.iter().map(String::as_str).collect::<Vec<_>>();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// Item names and short names matching what has been typed so far, best first.
+async fn autocomplete_item(ctx: poise::Context<'_, Bot, Error>, partial: &str) -> Vec<String> {
+    let Ok(items) = ctx.data().tarkov.items().await else {
+        return Vec::new();
+    };
+    search::rank(&items.value, partial, |item| {
+        [item.name.as_str(), item.short_name.as_str()]
+    })
+    .into_iter()
+    .take(MAX_MENU_CHOICES)
+    .map(|found| found.item.name.clone())
+    .collect()
+}
+
+/// Looks up the flea and trader prices of an item
+#[poise::command(prefix_command, slash_command, check = "permissions::check")]
+pub async fn price(
+    ctx: poise::Context<'_, Bot, Error>,
+    #[description = "Item name or short name"]
+    #[autocomplete = "autocomplete_item"]
+    #[rest]
+    item: String,
+) -> Result<(), Error> {
+    info!("PRICE invoked by {:?}", &ctx.author().name);
+    // Filling the item list and fetching prices can outlast the three seconds
+    ctx.defer().await?;
+
+    let items = ctx.data().tarkov.items().await?;
+    let matches = search::rank(&items.value, &item, |item| {
+        [item.name.as_str(), item.short_name.as_str()]
+    });
+    if matches.is_empty() {
+        ctx.say("No items found with that name").await?;
+        return Ok(());
+    }
+
+    if let Some(found) = search::best(&matches) {
+        let cached = ctx.data().tarkov.item(&found.item.id).await?;
+        let item =
+            Option::as_ref(&cached.value).ok_or("tarkov.dev does not know that item anymore")?;
+        let mut reply = CreateReply::default().embed(item_embed(item));
+        if let Some(note) = cached.note() {
+            reply = reply.content(note);
+        }
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    let content = if matches.len() > MAX_MENU_CHOICES {
+        format!(
+            "{} items match, showing the closest {MAX_MENU_CHOICES}. Be more specific to narrow it down",
+            matches.len()
+        )
+    } else {
+        format!("{} items match, pick one", matches.len())
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(vec![item_menu(&matches)]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Compares the rounds of a caliber against each armor class
+#[poise::command(prefix_command, check = "permissions::check")]
+pub async fn ammo(ctx: Context<'_>, #[rest] caliber: String) -> Result<(), Error> {
+    info!("AMMO invoked by {:?}", &ctx.author().name);
+
+    let cached = ctx.data.tarkov.ammo().await?;
+    let rounds = ammo::rounds(&cached.value, &caliber)?;
+    let png = chart::scatter(&rounds)?;
+    let embed = CreateEmbed::new()
+        .title(format!("{} ammo", rounds[0].caliber_name()))
+        .colour(Colour::DARK_GOLD)
+        .description(format!("{}\n{}", ammo::table(&rounds), ammo::LEGEND))
+        .image("attachment://ammo.png");
+    let mut reply = CreateReply::default()
+        .embed(embed)
+        .attachment(CreateAttachment::bytes(png, "ammo.png"));
+    if let Some(note) = cached.note() {
+        reply = reply.content(note);
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Shows what a hideout station level needs
+#[poise::command(
+    prefix_command,
+    check = "permissions::check",
+    subcommands("hideout_level")
+)]
+pub async fn hideout(ctx: Context<'_>, #[rest] station: String) -> Result<(), Error> {
+    info!("HIDEOUT invoked by {:?}", &ctx.author().name);
+
+    let (name, level) = split_level(&station);
+    let stations = ctx.data.tarkov.hideout().await?;
+    let station = hideout::find_station(&stations.value, name)?;
+    let built = ctx
+        .data
+        .quest_progress
+        .get(ctx.author().id)?
+        .hideout
+        .get(&station.name)
+        .copied();
+    let level = hideout::station_level(station, level, built)?;
+
+    let mut reply = CreateReply::default().embed(station_embed(station, level));
+    if let Some(note) = stations.note() {
+        reply = reply.content(note);
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "level", check = "permissions::check")]
+pub async fn hideout_level(ctx: Context<'_>, #[rest] station: String) -> Result<(), Error> {
+    info!("HIDEOUT LEVEL invoked by {:?}", &ctx.author().name);
+
+    let (name, Some(level)) = split_level(&station) else {
+        return Err("Use !hideout level <station> <level>, 0 if it is not built".into());
+    };
+    let stations = ctx.data.tarkov.hideout().await?;
+    let station = hideout::find_station(&stations.value, name)?;
+    let max = station.levels.len() as u32;
+    if level > max {
+        return Err(format!("{} only goes up to level {max}", station.name).into());
+    }
+    ctx.data
+        .quest_progress
+        .update(ctx.author().id, |progress| {
+            progress.hideout.insert(station.name.clone(), level)
+        })?;
+    ctx.say(format!("Your {} is now level {level}", station.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    check = "permissions::check",
+    subcommands("crafts_profitable")
+)]
+pub async fn crafts(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use !crafts profitable").await?;
+    Ok(())
+}
+
+/// Crafts listed by !crafts profitable, each takes two lines.
+const MAX_CRAFTS_SHOWN: usize = 15;
+
+/// Ranks crafts by profit per hour at current prices
+#[poise::command(prefix_command, rename = "profitable", check = "permissions::check")]
+pub async fn crafts_profitable(ctx: Context<'_>) -> Result<(), Error> {
+    info!("CRAFTS PROFITABLE invoked by {:?}", &ctx.author().name);
+
+    let levels = ctx.data.quest_progress.get(ctx.author().id)?.hideout;
+    let book = ctx.data.tarkov.crafts().await?;
+    let profits = hideout::profitable(&book.value, &levels);
+    if profits.is_empty() {
+        ctx.say("No craft turns a profit at current prices").await?;
+        return Ok(());
+    }
+
+    let shown = profits.len().min(MAX_CRAFTS_SHOWN);
+    let mut embed = CreateEmbed::new()
+        .title(format!("💰 Top {shown} crafts by profit per hour"))
+        .colour(Colour::DARK_GOLD)
+        .description(crafts_list(&profits[..shown]));
+    if levels.is_empty() {
+        embed = embed.footer(CreateEmbedFooter::new(
+            "Counting every station level, set yours with !hideout level <station> <level>",
+        ));
+    }
+    let mut reply = CreateReply::default().embed(embed);
+    if let Some(note) = book.note() {
+        reply = reply.content(note);
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, owners_only, subcommands("tarkov_refresh"))]
+pub async fn tarkov(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use !tarkov refresh").await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, rename = "refresh", owners_only)]
+pub async fn tarkov_refresh(ctx: Context<'_>) -> Result<(), Error> {
+    info!("TARKOV REFRESH invoked by {:?}", &ctx.author().name);
+
+    let count = ctx.data.tarkov.refresh_quests().await?;
+    ctx.say(format!("Refreshed data/quests.json, {count} quests"))
+        .await?;
+    Ok(())
+}
@@ -1,52 +1,194 @@
+use super::audio::{ProcessedInput, Processor, SourceTap};
+use super::autoplay;
 use super::bot::Error;
-use super::status::Status;
-use serenity::all::GuildId;
+use super::cache::AudioCache;
+use super::effects::{Effect, EffectChain};
+use super::history::{History, HistoryEntry};
+use super::loudness::{self, LoudnessCache, LoudnessMeter, LoudnessState};
+use super::providers::Providers;
+use super::session::{Session, SessionStore};
+use super::settings::{GuildSettings, Settings};
+use super::speech::Synthesizer;
+use super::status::{LoopMode, Status};
+use super::track::{track_key, CurrentTrack, QueuedTrack};
+use reqwest::Client as HttpClient;
+use serenity::all::{ChannelId, GuildId, UserId};
 use serenity::async_trait;
-use songbird::input::Input;
-use songbird::tracks::TrackHandle;
-use songbird::{Call, Event, EventContext, EventHandler as VoiceEventHandler, Songbird};
-use std::collections::VecDeque;
+use songbird::input::{File, Input};
+use songbird::tracks::{ReadyState, TrackHandle};
+use songbird::{
+    Call, Event, EventContext, EventHandler as VoiceEventHandler, Songbird, TrackEvent,
+};
+use std::collections::{HashSet, VecDeque};
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Notify;
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+const NORMALIZE_INTERVAL: Duration = Duration::from_secs(2);
+/// Blocks of 400ms to measure before trusting the estimate enough to act on it.
+const NORMALIZE_MIN_BLOCKS: usize = 10;
+/// Only remember measurements that covered a decent part of the track.
+const NORMALIZE_CACHE_BLOCKS: usize = 75;
+/// How often playing tracks report their position, to time preloads and fades.
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+/// How long before a transition the next track starts loading, yt-dlp
+/// regularly needs a few seconds to hand over a stream.
+const PRELOAD_LEAD: Duration = Duration::from_secs(10);
+pub const MAX_CROSSFADE_SECS: u64 = 12;
+const FADE_STEP: Duration = Duration::from_millis(50);
+/// Longest wait for the incoming track to buffer before fading anyway.
+const FADE_READY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Music volume while speech plays over it.
+const DUCK_LEVEL: f32 = 0.3;
+/// Longest the next track waits on its announcement.
+const ANNOUNCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub enum SkipVote {
+    Skipped,
+    Registered { votes: usize, needed: usize },
+}
+
+/// A track's stream with its normalization state, built ahead of the
+/// transition when preloading.
+struct PreparedTrack {
+    url: String,
+    input: Input,
+    gain: f32,
+    meter: Option<Arc<Mutex<LoudnessState>>>,
+}
+
+/// A track on its way out during a crossfade.
+#[derive(Clone)]
+struct Fading {
+    handle: TrackHandle,
+    gain: f32,
+}
+
 #[derive(Clone)]
 pub struct Driver {
-    current_track: Arc<Mutex<Option<TrackHandle>>>,
+    http_client: HttpClient,
+    current_track: Arc<Mutex<Option<CurrentTrack>>>,
     status: Arc<Mutex<Status>>,
-    queue: Arc<Mutex<VecDeque<Input>>>,
+    queue: Arc<Mutex<VecDeque<QueuedTrack>>>,
     notify: Arc<Notify>,
+    volume: Arc<Mutex<f32>>,
+    loop_mode: Arc<Mutex<LoopMode>>,
+    // Where to seek the next track to, set when restoring a session
+    resume_position: Arc<Mutex<Option<Duration>>>,
+    connection: Arc<Mutex<Option<(GuildId, ChannelId)>>>,
+    skip_votes: Arc<Mutex<HashSet<UserId>>>,
+    // Requesters ordered from least to most recently served, for fair mode
+    served: Arc<Mutex<Vec<Option<UserId>>>>,
+    history: Arc<Mutex<History>>,
+    autoplay_pending: Arc<Mutex<bool>>,
+    // Normalization gain of the current track, applied on top of the volume
+    gain: Arc<Mutex<f32>>,
+    loudness: LoudnessCache,
+    // Effect the processing chain of the current track reads from
+    effect: Arc<Mutex<Option<Effect>>>,
+    preloaded: Arc<Mutex<Option<PreparedTrack>>>,
+    // Tracks still playing out under the current one, their end is not a
+    // signal to advance the queue
+    fading: Arc<Mutex<Vec<Fading>>>,
+    // Set when the next track should fade in rather than cut
+    crossfade_pending: Arc<Mutex<bool>>,
+    // Clips mixed over the music, they play next to the queue, not in it
+    overlays: Arc<Mutex<Vec<TrackHandle>>>,
+    // Speech overlays still playing, the music stays ducked until none are
+    speaking: Arc<Mutex<usize>>,
+    speech: Arc<dyn Synthesizer>,
+    cache: AudioCache,
+    settings: Settings,
+    sessions: SessionStore,
+    // The one session saver task, replaced on connect and stopped on leave
+    session_saver: Arc<Mutex<Option<JoinHandle<()>>>>,
 }
 
 impl Driver {
-    pub fn new() -> Self {
+    pub fn new(
+        http_client: HttpClient,
+        settings: Settings,
+        cache: AudioCache,
+        speech: Arc<dyn Synthesizer>,
+    ) -> Self {
         Self {
+            http_client,
+            settings,
+            cache,
+            speech,
             current_track: Arc::new(Mutex::new(None)),
             notify: Arc::new(Notify::new()),
             queue: Arc::new(Mutex::new(VecDeque::new())),
             status: Arc::new(Mutex::new(Status::Disconnected)),
+            volume: Arc::new(Mutex::new(1.0)),
+            loop_mode: Arc::new(Mutex::new(LoopMode::Off)),
+            resume_position: Arc::new(Mutex::new(None)),
+            connection: Arc::new(Mutex::new(None)),
+            skip_votes: Arc::new(Mutex::new(HashSet::new())),
+            served: Arc::new(Mutex::new(Vec::new())),
+            history: Arc::new(Mutex::new(History::default())),
+            autoplay_pending: Arc::new(Mutex::new(false)),
+            gain: Arc::new(Mutex::new(1.0)),
+            loudness: LoudnessCache::new(),
+            effect: Arc::new(Mutex::new(None)),
+            preloaded: Arc::new(Mutex::new(None)),
+            fading: Arc::new(Mutex::new(Vec::new())),
+            crossfade_pending: Arc::new(Mutex::new(false)),
+            overlays: Arc::new(Mutex::new(Vec::new())),
+            speaking: Arc::new(Mutex::new(0)),
+            sessions: SessionStore::new(),
+            session_saver: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub async fn player(&self, call: Arc<tokio::sync::Mutex<Call>>) {
-        let call = Arc::clone(&call);
-        let notify = Arc::clone(&self.notify);
-        let queue = Arc::clone(&self.queue);
-        let status = Arc::clone(&self.status);
-        let current_track = Arc::clone(&self.current_track);
+    /// Joins the voice channel and spawns the player and session saver tasks.
+    pub async fn connect(
+        &self,
+        manager: Arc<Songbird>,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<(), Error> {
+        let call = manager.join(guild_id, channel_id).await?;
 
         call.lock()
             .await
             .add_global_event(Event::Track(songbird::TrackEvent::End), self.clone());
 
         {
-            // Use scopes to release locks
-            // since we just need to use this mutex
-            // one time out of the loop
-            let mut status = status.lock().unwrap();
+            let mut connection = self.connection.lock().unwrap();
+            *connection = Some((guild_id, channel_id));
+            let mut status = self.status.lock().unwrap();
             *status = Status::Idle;
         }
 
+        let driver = self.clone();
+        tokio::spawn(async move {
+            driver.player(call).await;
+        });
+
+        let driver = self.clone();
+        let saver = tokio::spawn(async move {
+            driver.session_saver().await;
+        });
+        if let Some(previous) = self.session_saver.lock().unwrap().replace(saver) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn player(&self, call: Arc<tokio::sync::Mutex<Call>>) {
+        let call = Arc::clone(&call);
+        let notify = Arc::clone(&self.notify);
+        let queue = Arc::clone(&self.queue);
+        let status = Arc::clone(&self.status);
+        let current_track = Arc::clone(&self.current_track);
+
         loop {
             notify.notified().await;
             let mut manager = call.lock().await;
@@ -60,20 +202,445 @@ impl Driver {
                 break;
             }
 
+            // Speaking needs the call, so let go of it until the
+            // announcement is over
+            if let Some(text) = self.announcement() {
+                drop(manager);
+                self.announce(Arc::clone(&call), &text).await;
+                manager = call.lock().await;
+            }
+
             let mut queue = queue.lock().unwrap();
             let mut status = status.lock().unwrap();
-            if let Some(song) = queue.pop_front() {
+            let next = match self.next_index(&queue) {
+                Some(index) => {
+                    self.mark_served(&queue[index]);
+                    queue.remove(index)
+                }
+                None => None,
+            };
+            if let Some(track) = next {
                 // Need to grab all associated locks
                 let mut current_track = current_track.lock().unwrap();
-                let track_handle = manager.play_only_input(song);
-                *current_track = Some(track_handle);
+                let settings = self.guild_settings();
+                let key = track_key(&track.url);
+                let prepared = match self.preloaded.lock().unwrap().take() {
+                    Some(prepared) if prepared.url == track.url => prepared,
+                    _ => self.prepare(&track, &settings),
+                };
+                let gain = prepared.gain;
+                *self.gain.lock().unwrap() = gain;
+                *self.effect.lock().unwrap() = settings.effect.clone();
+
+                // Crossfades keep the outgoing track mixed in, anything else
+                // cuts the music still playing. Overlays and speech go on
+                let crossfade = std::mem::take(&mut *self.crossfade_pending.lock().unwrap());
+                if !crossfade {
+                    if let Some(previous) = current_track.as_ref() {
+                        let _ = previous.handle.stop();
+                    }
+                    self.stop_fading();
+                }
+                let handle = manager.play_input(prepared.input);
+                let volume = if crossfade { 0.0 } else { self.level() * gain };
+                if let Err(e) = handle.set_volume(volume) {
+                    warn!("Could not set volume on new track: {e}");
+                }
+                if crossfade {
+                    let driver = self.clone();
+                    let handle = handle.clone();
+                    let length = Duration::from_secs(settings.crossfade_secs);
+                    tokio::spawn(async move {
+                        driver.crossfade(handle, length).await;
+                    });
+                }
+                // A crossfade leaves no gap to announce in, so it goes over
+                // the start of the track
+                if settings.announce && crossfade {
+                    let driver = self.clone();
+                    let call = Arc::clone(&call);
+                    let text = format!("Now playing: {}", track.display_title());
+                    tokio::spawn(async move {
+                        if let Err(e) = driver.speak(call, &text).await {
+                            warn!("Could not announce track: {e}");
+                        }
+                    });
+                }
+                if let Some(duration) = track.duration {
+                    let watcher = TrackPosition {
+                        driver: self.clone(),
+                        duration,
+                        crossfade: Duration::from_secs(settings.crossfade_secs),
+                        preloading: AtomicBool::new(false),
+                    };
+                    if let Err(e) =
+                        handle.add_event(Event::Periodic(POSITION_INTERVAL, None), watcher)
+                    {
+                        warn!("Could not watch track position: {e}");
+                    }
+                }
+                if let Some(state) = prepared.meter {
+                    let driver = self.clone();
+                    let handle = handle.clone();
+                    tokio::spawn(async move {
+                        driver
+                            .normalize(handle, state, key, settings.target_lufs)
+                            .await;
+                    });
+                }
+                if let Some(position) = self.resume_position.lock().unwrap().take() {
+                    // The callback only reports when the seek lands, nothing to wait on
+                    let _ = handle.seek(position);
+                }
+
+                *current_track = Some(CurrentTrack {
+                    track,
+                    handle,
+                    started_at: SystemTime::now(),
+                });
                 *status = Status::Playing;
+                self.skip_votes.lock().unwrap().clear();
             } else {
                 *status = Status::Idle;
             }
         }
     }
 
+    /// Builds the stream for `track`. It is always processed so effects can be
+    /// switched mid-track, with the meter first to measure the track as uploaded.
+    fn prepare(&self, track: &QueuedTrack, settings: &GuildSettings) -> PreparedTrack {
+        let key = track_key(&track.url);
+        let (input, tap) = match self.cache.lookup(&key) {
+            Some(path) => (File::new(path).into(), None),
+            None => {
+                let cache = self.cache.clone();
+                let cache_key = key.clone();
+                let tap: SourceTap = Arc::new(move |source| cache.tee(&cache_key, source));
+                (
+                    Providers::stream_for(self.http_client.clone(), track.url.clone()),
+                    Some(tap),
+                )
+            }
+        };
+
+        // Replays start at the cached level, new tracks get measured
+        let mut meter = None;
+        let mut gain = 1.0;
+        if settings.normalize {
+            match self.loudness.get(&key) {
+                Some(lufs) => gain = loudness::gain_for(lufs, settings.target_lufs),
+                None => meter = Some(Arc::new(Mutex::new(LoudnessState::default()))),
+            }
+        }
+
+        let meter_state = meter.clone();
+        let effect = Arc::clone(&self.effect);
+        let input = ProcessedInput::wrap(
+            input,
+            Arc::new(move || {
+                let mut processors: Vec<Box<dyn Processor>> = Vec::new();
+                if let Some(state) = &meter_state {
+                    processors.push(Box::new(LoudnessMeter::new(Arc::clone(state))));
+                }
+                processors.push(Box::new(EffectChain::new(Arc::clone(&effect))));
+                processors
+            }),
+            tap,
+        );
+
+        PreparedTrack {
+            url: track.url.clone(),
+            input,
+            gain,
+            meter,
+        }
+    }
+
+    /// Index of the track the player will take next, honouring fair mode.
+    fn next_index(&self, queue: &VecDeque<QueuedTrack>) -> Option<usize> {
+        if self.fair_queue() && self.loop_mode() != LoopMode::Track {
+            fair_index(queue, &self.served.lock().unwrap())
+        } else if queue.is_empty() {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    fn mark_served(&self, track: &QueuedTrack) {
+        let mut served = self.served.lock().unwrap();
+        served.retain(|user| *user != track.requester);
+        served.push(track.requester);
+    }
+
+    /// Opens the stream of the track that will play next, so the transition
+    /// does not wait on yt-dlp.
+    async fn preload(&self) {
+        let track = {
+            let queue = self.queue.lock().unwrap();
+            let next = match self.loop_mode() {
+                LoopMode::Track => self
+                    .current_track
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|current| current.track.clone()),
+                _ => self.next_index(&queue).map(|index| queue[index].clone()),
+            };
+            match next {
+                Some(track) => track,
+                None => return,
+            }
+        };
+
+        let prepared = self.prepare(&track, &self.guild_settings());
+        match prepared.input.make_live_async().await {
+            Ok(input) => {
+                *self.preloaded.lock().unwrap() = Some(PreparedTrack { input, ..prepared });
+            }
+            Err(e) => warn!("Could not preload {}: {e}", track.display_title()),
+        }
+    }
+
+    /// Hands the queue over to the next track while `handle` plays out, if
+    /// there is anything to fade into.
+    fn start_crossfade(&self, handle: &TrackHandle) {
+        let mut queue = self.queue.lock().unwrap();
+        let finished = {
+            let mut current_track = self.current_track.lock().unwrap();
+            if current_track
+                .as_ref()
+                .is_none_or(|current| current.handle.uuid() != handle.uuid())
+            {
+                return;
+            }
+            // Let it end normally so autoplay gets a chance
+            if queue.is_empty() && self.loop_mode() == LoopMode::Off {
+                return;
+            }
+            current_track.take().unwrap()
+        };
+
+        self.fading.lock().unwrap().push(Fading {
+            handle: finished.handle.clone(),
+            gain: *self.gain.lock().unwrap(),
+        });
+        self.requeue(&mut queue, finished);
+        *self.crossfade_pending.lock().unwrap() = true;
+        self.notify.notify_one();
+    }
+
+    /// Ramps `incoming` up and the fading tracks down over `length`, then
+    /// stops the fading tracks.
+    async fn crossfade(&self, incoming: TrackHandle, length: Duration) {
+        let waiting = Instant::now();
+        while waiting.elapsed() < FADE_READY_TIMEOUT {
+            match incoming.get_info().await {
+                Ok(state) if state.ready != ReadyState::Playable => {
+                    tokio::time::sleep(FADE_STEP).await
+                }
+                _ => break,
+            }
+        }
+
+        let outgoing = self.fading.lock().unwrap().clone();
+        let steps = (length.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+        let mut interval = tokio::time::interval(FADE_STEP);
+        for step in 1..=steps {
+            interval.tick().await;
+            // Equal power curves, so the overlap does not dip in the middle
+            let progress = step as f32 / steps as f32 * FRAC_PI_2;
+            let volume = self.level();
+            if self.is_current(&incoming) {
+                let gain = *self.gain.lock().unwrap();
+                let _ = incoming.set_volume(volume * gain * progress.sin());
+            }
+            for track in &outgoing {
+                let _ = track
+                    .handle
+                    .set_volume(volume * track.gain * progress.cos());
+            }
+        }
+
+        for track in outgoing {
+            let _ = track.handle.stop();
+        }
+    }
+
+    /// Mixes `input` over whatever is playing without touching the queue.
+    pub async fn play_overlay(
+        &self,
+        manager: Arc<Songbird>,
+        input: Input,
+        volume: f32,
+    ) -> Result<TrackHandle, Error> {
+        let guild_id = self
+            .guild()
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        let call = manager
+            .get(guild_id)
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        self.overlay(call, input, volume).await
+    }
+
+    /// Plays `track` from `offset` next to the queue, for games that only
+    /// want an excerpt. Stopping the returned handle cuts it.
+    pub async fn play_snippet(
+        &self,
+        manager: Arc<Songbird>,
+        track: &QueuedTrack,
+        offset: Duration,
+    ) -> Result<TrackHandle, Error> {
+        if *self.status.lock().unwrap() != Status::Idle {
+            return Err("Something is playing, stop the music first".into());
+        }
+        let guild_id = self
+            .guild()
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        let call = manager
+            .get(guild_id)
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+
+        let prepared = self.prepare(track, &self.guild_settings());
+        let handle = self
+            .overlay(call, prepared.input, self.level() * prepared.gain)
+            .await?;
+        // The seek lands once the stream is live, nothing to wait on
+        let _ = handle.seek(offset);
+        Ok(handle)
+    }
+
+    async fn overlay(
+        &self,
+        call: Arc<tokio::sync::Mutex<Call>>,
+        input: Input,
+        volume: f32,
+    ) -> Result<TrackHandle, Error> {
+        let mut call = call.lock().await;
+        let handle = call.play_input(input);
+        self.overlays.lock().unwrap().push(handle.clone());
+        handle.set_volume(volume)?;
+        Ok(handle)
+    }
+
+    /// Speaks `text` in the voice channel, ducking the music meanwhile.
+    pub async fn say(&self, manager: Arc<Songbird>, text: &str) -> Result<(), Error> {
+        let guild_id = self
+            .guild()
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        let call = manager
+            .get(guild_id)
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        self.speak(call, text).await?;
+        Ok(())
+    }
+
+    /// What to say before the next track starts, if the guild wants
+    /// announcements and the track isn't faded into.
+    fn announcement(&self) -> Option<String> {
+        if !self.guild_settings().announce || *self.crossfade_pending.lock().unwrap() {
+            return None;
+        }
+        let queue = self.queue.lock().unwrap();
+        let index = self.next_index(&queue)?;
+        Some(format!("Now playing: {}", queue[index].display_title()))
+    }
+
+    /// Speaks `text` and waits until it is over.
+    async fn announce(&self, call: Arc<tokio::sync::Mutex<Call>>, text: &str) {
+        let handle = match self.speak(call, text).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                warn!("Could not announce track: {e}");
+                return;
+            }
+        };
+        let ended = Arc::new(Notify::new());
+        let waiter = TrackEnded {
+            notify: Arc::clone(&ended),
+        };
+        // An error means it is already over
+        if handle
+            .add_event(Event::Track(TrackEvent::End), waiter)
+            .is_ok()
+        {
+            let _ = tokio::time::timeout(ANNOUNCE_TIMEOUT, ended.notified()).await;
+        }
+    }
+
+    async fn speak(
+        &self,
+        call: Arc<tokio::sync::Mutex<Call>>,
+        text: &str,
+    ) -> Result<TrackHandle, Error> {
+        let speech = self.speech.synthesize(text).await?;
+        let handle = self.overlay(call, speech.into(), 1.0).await?;
+
+        *self.speaking.lock().unwrap() += 1;
+        self.apply_volume()?;
+        let unduck = Unduck {
+            driver: self.clone(),
+        };
+        if handle
+            .add_event(Event::Track(TrackEvent::End), unduck)
+            .is_err()
+        {
+            // Already over before we could listen for it
+            self.unduck();
+        }
+        Ok(handle)
+    }
+
+    fn unduck(&self) {
+        {
+            let mut speaking = self.speaking.lock().unwrap();
+            *speaking = speaking.saturating_sub(1);
+        }
+        if let Err(e) = self.apply_volume() {
+            warn!("Could not restore volume after speech: {e}");
+        }
+    }
+
+    /// Volume for the music right now, lowered while speech plays.
+    fn level(&self) -> f32 {
+        match *self.speaking.lock().unwrap() {
+            0 => self.volume(),
+            _ => self.volume() * DUCK_LEVEL,
+        }
+    }
+
+    /// Sets the current track to the volume it should have right now. A
+    /// running crossfade picks changes up on its next step instead.
+    fn apply_volume(&self) -> Result<(), Error> {
+        if !self.fading.lock().unwrap().is_empty() {
+            return Ok(());
+        }
+        if let Some(ref current) = *self.current_track.lock().unwrap() {
+            current
+                .handle
+                .set_volume(self.level() * *self.gain.lock().unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Cuts any track still fading out.
+    fn stop_fading(&self) {
+        for track in self.fading.lock().unwrap().iter() {
+            let _ = track.handle.stop();
+        }
+    }
+
+    /// Puts a track that played through back into the queue as the loop mode
+    /// asks and records it in the history.
+    fn requeue(&self, queue: &mut VecDeque<QueuedTrack>, finished: CurrentTrack) {
+        match self.loop_mode() {
+            LoopMode::Track => queue.push_front(finished.track.clone()),
+            LoopMode::Queue => queue.push_back(finished.track.clone()),
+            LoopMode::Off => {}
+        }
+        self.record_history(Some(finished), false);
+    }
+
     pub async fn leave(&self, manager: Arc<Songbird>, guild_id: GuildId) -> Result<(), Error> {
         if let Some(call) = manager.get(guild_id) {
             let mut call = call.lock().await;
@@ -86,14 +653,27 @@ impl Driver {
             let mut current_track = self.current_track.lock().unwrap();
             queue.clear();
 
-            if let Some(ref track) = *current_track {
-                if let Err(e) = track.stop() {
+            if let Some(ref current) = *current_track {
+                if let Err(e) = current.handle.stop() {
                     error!("Error stopping current track when leaving: {e}");
                 }
             }
-            *current_track = None;
+            // Leaving says nothing about the track, so it is not a skip
+            self.record_history(current_track.take(), false);
+            self.stop_fading();
+            *self.preloaded.lock().unwrap() = None;
+            *self.speaking.lock().unwrap() = 0;
+            *self.connection.lock().unwrap() = None;
+            if let Some(saver) = self.session_saver.lock().unwrap().take() {
+                saver.abort();
+            }
             self.notify.notify_one();
 
+            // Leaving on purpose means there is nothing to resume
+            if let Err(e) = self.sessions.remove(guild_id) {
+                warn!("Could not clear saved session: {e}");
+            }
+
             return Ok(());
         }
 
@@ -103,15 +683,15 @@ impl Driver {
     pub async fn skip_current_track(&self) -> Result<(), Error> {
         let mut current_track = self.current_track.lock().unwrap();
 
-        if let Some(track) = &mut *current_track {
+        if let Some(current) = &mut *current_track {
             match *self.status.lock().unwrap() {
                 Status::Playing => {
-                    track.stop()?;
-                    *current_track = None
+                    current.handle.stop()?;
+                    self.record_history(current_track.take(), true);
                 }
                 Status::Paused => {
-                    track.stop()?;
-                    *current_track = None;
+                    current.handle.stop()?;
+                    self.record_history(current_track.take(), true);
                 }
                 _ => error!("Attempting to skip in a none supported state"),
             }
@@ -121,6 +701,120 @@ impl Driver {
         Err("There is nothing to skip".into())
     }
 
+    /// Registers `voter`'s skip vote for the current track and skips it once
+    /// the votes from people still in `listeners` reach `percent` of them.
+    pub async fn vote_skip(
+        &self,
+        voter: UserId,
+        listeners: &HashSet<UserId>,
+        percent: u8,
+    ) -> Result<SkipVote, Error> {
+        if self.current_track.lock().unwrap().is_none() {
+            return Err("There is nothing to skip".into());
+        }
+        if !listeners.contains(&voter) {
+            return Err("You need to be in the voice channel to vote".into());
+        }
+
+        let (votes, needed) = {
+            let mut skip_votes = self.skip_votes.lock().unwrap();
+            skip_votes.insert(voter);
+            skip_votes.retain(|user| listeners.contains(user));
+            let needed = (listeners.len() * percent as usize).div_ceil(100).max(1);
+            (skip_votes.len(), needed)
+        };
+
+        if votes >= needed {
+            self.skip_current_track().await?;
+            return Ok(SkipVote::Skipped);
+        }
+        Ok(SkipVote::Registered { votes, needed })
+    }
+
+    /// Queues the last finished track again as a request from `requester`.
+    /// It plays next unless fair mode decides otherwise.
+    pub fn back(&self, requester: UserId) -> Result<QueuedTrack, Error> {
+        let guild_id = self
+            .guild()
+            .ok_or("Not connected in a voice channel, use !join to connect")?;
+        // Queue before history, the order requeue takes them in
+        let mut queue = self.queue.lock().unwrap();
+        let mut history = self.history.lock().unwrap();
+        let mut track = history
+            .recent(guild_id, 1)
+            .pop()
+            .ok_or("Nothing has played yet")?
+            .track;
+        track.requester = Some(requester);
+
+        check_limits(&self.settings.get(guild_id), &queue, &track)?;
+        history.pop_latest(guild_id);
+        if self.fair_queue() {
+            queue.push_back(track.clone());
+        } else {
+            queue.push_front(track.clone());
+        }
+        if *self.status.lock().unwrap() == Status::Idle {
+            self.notify.notify_one();
+        }
+        Ok(track)
+    }
+
+    /// Restarts the current track from the beginning.
+    pub fn replay(&self) -> Result<(), Error> {
+        match *self.current_track.lock().unwrap() {
+            Some(ref current) => {
+                // Nothing to wait on, the seek is reported through the callback
+                let _ = current.handle.seek(Duration::ZERO);
+                Ok(())
+            }
+            None => Err("There is no track to replay".into()),
+        }
+    }
+
+    pub fn history(&self, count: usize) -> Vec<HistoryEntry> {
+        match self.guild() {
+            Some(guild_id) => self.history.lock().unwrap().recent(guild_id, count),
+            None => Vec::new(),
+        }
+    }
+
+    fn record_history(&self, finished: Option<CurrentTrack>, skipped: bool) {
+        let (Some(finished), Some(guild_id)) = (finished, self.guild()) else {
+            return;
+        };
+        self.history.lock().unwrap().push(
+            guild_id,
+            HistoryEntry {
+                track: finished.track,
+                started_at: finished.started_at,
+                skipped,
+            },
+        );
+    }
+
+    pub fn current_requester(&self) -> Option<UserId> {
+        self.current_track
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|current| current.track.requester)
+    }
+
+    pub fn guild(&self) -> Option<GuildId> {
+        self.connection
+            .lock()
+            .unwrap()
+            .map(|(guild_id, _)| guild_id)
+    }
+
+    pub fn channel(&self) -> Option<ChannelId> {
+        self.connection
+            .lock()
+            .unwrap()
+            .map(|(_, channel_id)| channel_id)
+    }
+
     pub async fn pause_current_track(&self) -> Result<(), Error> {
         let current_track = self.current_track.lock().unwrap();
         let mut status = self.status.lock().unwrap();
@@ -129,8 +823,9 @@ impl Driver {
             return Err("There is no track to pause".into());
         }
 
-        let input = current_track.as_ref().unwrap();
-        if let Err(e) = input.pause() {
+        let current = current_track.as_ref().unwrap();
+        self.stop_fading();
+        if let Err(e) = current.handle.pause() {
             error!("Error pausing track:{}", e);
             return Err("Error pausing track".into());
         }
@@ -146,8 +841,8 @@ impl Driver {
             return Err("There is no track to play".into());
         }
 
-        let input = current_track.as_ref().unwrap();
-        if let Err(e) = input.play() {
+        let current = current_track.as_ref().unwrap();
+        if let Err(e) = current.handle.play() {
             let error_message = format!("Error unpausing track: {e}");
             error!(error_message);
             return Err(error_message.into());
@@ -156,20 +851,26 @@ impl Driver {
         Ok(())
     }
 
-    pub async fn enqueue_input(&self, input: Input) -> Result<(), Error> {
+    pub async fn enqueue_input(&self, track: QueuedTrack) -> Result<(), Error> {
+        let settings = match *self.connection.lock().unwrap() {
+            Some((guild_id, _)) => self.settings.get(guild_id),
+            None => Default::default(),
+        };
         let mut queue = self.queue.lock().unwrap();
         let status = self.status.lock().unwrap();
 
+        check_limits(&settings, &queue, &track)?;
+
         match *status {
             Status::Idle => {
                 let was_empty = queue.is_empty();
-                queue.push_back(input);
+                queue.push_back(track);
                 if was_empty {
                     self.notify.notify_one();
                 }
             }
             Status::Playing | Status::Paused => {
-                queue.push_back(input);
+                queue.push_back(track);
             }
             Status::Disconnected => {
                 return Err("Not connected in a voice channel, use !join to connect".into())
@@ -177,13 +878,359 @@ impl Driver {
         }
         Ok(())
     }
+
+    pub fn volume(&self) -> f32 {
+        *self.volume.lock().unwrap()
+    }
+
+    pub fn set_volume(&self, volume: f32) -> Result<(), Error> {
+        *self.volume.lock().unwrap() = volume;
+        self.apply_volume()
+    }
+
+    pub fn effect(&self) -> Option<Effect> {
+        self.effect.lock().unwrap().clone()
+    }
+
+    /// Switches the effect of the track playing in `guild_id`, if any.
+    pub fn set_effect(&self, guild_id: GuildId, effect: Option<Effect>) {
+        if self.guild() == Some(guild_id) {
+            *self.effect.lock().unwrap() = effect;
+        }
+    }
+
+    /// Settings of the guild we are connected to, defaults when disconnected.
+    fn guild_settings(&self) -> GuildSettings {
+        match self.guild() {
+            Some(guild_id) => self.settings.get(guild_id),
+            None => GuildSettings::default(),
+        }
+    }
+
+    fn fair_queue(&self) -> bool {
+        self.guild_settings().fair_queue
+    }
+
+    fn autoplay_enabled(&self) -> bool {
+        self.guild_settings().autoplay
+    }
+
+    fn is_current(&self, handle: &TrackHandle) -> bool {
+        self.current_track
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|current| current.handle.uuid() == handle.uuid())
+    }
+
+    /// Moves the volume of a track being measured toward the target as the
+    /// estimate firms up, and caches the measurement once the track is done.
+    async fn normalize(
+        &self,
+        handle: TrackHandle,
+        state: Arc<Mutex<LoudnessState>>,
+        key: String,
+        target_lufs: f32,
+    ) {
+        let mut interval = tokio::time::interval(NORMALIZE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (lufs, blocks) = {
+                let state = state.lock().unwrap();
+                (state.integrated_lufs(), state.block_count())
+            };
+
+            if !self.is_current(&handle) {
+                if let (Some(lufs), true) = (lufs, blocks >= NORMALIZE_CACHE_BLOCKS) {
+                    if let Err(e) = self.loudness.insert(key, lufs) {
+                        warn!("Could not cache track loudness: {e}");
+                    }
+                }
+                break;
+            }
+
+            if let (Some(lufs), true) = (lufs, blocks >= NORMALIZE_MIN_BLOCKS) {
+                let gain = loudness::gain_for(lufs, target_lufs);
+                *self.gain.lock().unwrap() = gain;
+                if let Err(e) = self.apply_volume() {
+                    warn!("Could not apply normalization gain: {e}");
+                }
+            }
+        }
+    }
+
+    /// The track playing right now and how far into it we are.
+    pub async fn now_playing(&self) -> Option<(QueuedTrack, Duration)> {
+        let (track, handle) = match *self.current_track.lock().unwrap() {
+            Some(ref current) => (current.track.clone(), current.handle.clone()),
+            None => return None,
+        };
+        let position = handle
+            .get_info()
+            .await
+            .map(|state| state.position)
+            .unwrap_or_default();
+        Some((track, position))
+    }
+
+    /// The current track followed by everything queued after it.
+    pub fn queued_tracks(&self) -> Vec<QueuedTrack> {
+        let current = self
+            .current_track
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|current| current.track.clone());
+        current
+            .into_iter()
+            .chain(self.queue.lock().unwrap().iter().cloned())
+            .collect()
+    }
+
+    pub fn upcoming(&self, count: usize) -> (Vec<QueuedTrack>, usize) {
+        let queue = self.queue.lock().unwrap();
+        (queue.iter().take(count).cloned().collect(), queue.len())
+    }
+
+    /// Queues a track related to the last one played, skipping anything
+    /// that is already queued or was played recently.
+    async fn autoplay_next(&self) -> Result<(), Error> {
+        let recent = self.history(20);
+        let Some(seed) = recent.first().map(|entry| entry.track.clone()) else {
+            return Ok(());
+        };
+
+        let mut exclude: HashSet<String> = recent
+            .iter()
+            .map(|entry| track_key(&entry.track.url))
+            .collect();
+        exclude.extend(
+            self.queue
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|track| track_key(&track.url)),
+        );
+
+        let candidates = autoplay::related(self.http_client.clone(), &seed).await?;
+        match autoplay::pick(candidates, &exclude) {
+            Some(track) => {
+                info!("Autoplay picked {}", track.display_title());
+                self.enqueue_input(track).await
+            }
+            None => Err("Autoplay found nothing new to play".into()),
+        }
+    }
+
+    pub fn loop_mode(&self) -> LoopMode {
+        *self.loop_mode.lock().unwrap()
+    }
+
+    pub fn set_loop_mode(&self, mode: LoopMode) {
+        *self.loop_mode.lock().unwrap() = mode;
+    }
+
+    /// Restores the saved session for `guild_id`: queue, volume, loop mode and
+    /// the position in the track that was playing, then rejoins its channel.
+    pub async fn resume_session(
+        &self,
+        manager: Arc<Songbird>,
+        guild_id: GuildId,
+    ) -> Result<(), Error> {
+        if *self.status.lock().unwrap() != Status::Disconnected {
+            return Err("Already connected, use !leave before resuming a session".into());
+        }
+
+        let session = self
+            .sessions
+            .load(guild_id)?
+            .ok_or("There is no saved session to resume")?;
+        info!(
+            "Resuming session in {guild_id} with {} queued tracks",
+            session.queue.len()
+        );
+
+        {
+            let mut queue = self.queue.lock().unwrap();
+            queue.clear();
+            if let Some(current) = session.current {
+                queue.push_back(current);
+                *self.resume_position.lock().unwrap() =
+                    Some(Duration::from_secs(session.position_secs));
+            }
+            queue.extend(session.queue);
+        }
+        self.set_volume(session.volume)?;
+        self.set_loop_mode(session.loop_mode);
+
+        self.connect(manager, guild_id, ChannelId::new(session.channel_id))
+            .await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Resumes whichever guild saved a session most recently.
+    pub async fn resume_latest_session(&self, manager: Arc<Songbird>) -> Result<(), Error> {
+        match self.sessions.latest()? {
+            Some((guild_id, _)) => self.resume_session(manager, guild_id).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn session_saver(&self) {
+        let mut interval = tokio::time::interval(SESSION_SAVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some((guild_id, channel_id)) = *self.connection.lock().unwrap() else {
+                break;
+            };
+
+            let (current, handle) = match *self.current_track.lock().unwrap() {
+                Some(ref current) => (Some(current.track.clone()), Some(current.handle.clone())),
+                None => (None, None),
+            };
+            let position = match handle {
+                Some(handle) => handle
+                    .get_info()
+                    .await
+                    .map(|state| state.position)
+                    .unwrap_or_default(),
+                None => Duration::ZERO,
+            };
+
+            let session = Session {
+                channel_id: channel_id.get(),
+                current,
+                position_secs: position.as_secs(),
+                queue: self.queue.lock().unwrap().iter().cloned().collect(),
+                loop_mode: self.loop_mode(),
+                volume: self.volume(),
+                saved_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            };
+
+            // A concurrent !leave may have cleared the session in the meantime
+            if self.connection.lock().unwrap().is_none() {
+                break;
+            }
+            if let Err(e) = self.sessions.save(guild_id, session) {
+                warn!("Could not save session: {e}");
+            }
+        }
+    }
+}
+
+/// Rejects `track` if it breaks the guild's length or per user limits. A
+/// track of unknown length is rejected whenever a length limit is set.
+fn check_limits(
+    settings: &GuildSettings,
+    queue: &VecDeque<QueuedTrack>,
+    track: &QueuedTrack,
+) -> Result<(), Error> {
+    if let Some(limit) = settings.max_track_length_secs {
+        let Some(duration) = track.duration else {
+            return Err(format!(
+                "Can't tell how long {} is, the limit is {}",
+                track.display_title(),
+                format_duration(limit)
+            )
+            .into());
+        };
+        if duration.as_secs() > limit {
+            return Err(format!(
+                "{} is {}, the limit is {}",
+                track.display_title(),
+                format_duration(duration.as_secs()),
+                format_duration(limit)
+            )
+            .into());
+        }
+    }
+    if let Some(limit) = settings.max_tracks_per_user {
+        let queued = queue
+            .iter()
+            .filter(|queued| queued.requester == track.requester)
+            .count();
+        if queued >= limit {
+            return Err(
+                format!("You already have {queued} tracks queued, the limit is {limit}").into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Finds the first queued track of the requester who was served least recently,
+/// so one user flooding the queue can't starve everyone else.
+fn fair_index(queue: &VecDeque<QueuedTrack>, served: &[Option<UserId>]) -> Option<usize> {
+    let rank = |requester: &Option<UserId>| {
+        served
+            .iter()
+            .position(|user| user == requester)
+            .map_or(0, |position| position + 1)
+    };
+    queue
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, track)| rank(&track.requester))
+        .map(|(index, _)| index)
+}
+
+pub fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
 }
 
 #[async_trait]
 impl VoiceEventHandler for Driver {
-    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        // Tracks that faded out already handed the queue over, and overlays
+        // were never part of it
+        if let EventContext::Track(tracks) = ctx {
+            let ended = |handle: &TrackHandle| {
+                tracks
+                    .iter()
+                    .any(|(_, ended)| ended.uuid() == handle.uuid())
+            };
+            let mut fading = self.fading.lock().unwrap();
+            let mut overlays = self.overlays.lock().unwrap();
+            let before = fading.len() + overlays.len();
+            fading.retain(|track| !ended(&track.handle));
+            overlays.retain(|handle| !ended(handle));
+            if fading.len() + overlays.len() < before {
+                return None;
+            }
+        }
+
         let queue = Arc::clone(&self.queue);
-        let queue = queue.lock().unwrap();
+        let mut queue = queue.lock().unwrap();
+
+        // Skipped and stopped tracks have already been taken out of
+        // current_track, so only tracks that ran to the end loop
+        let finished = {
+            let mut current_track = self.current_track.lock().unwrap();
+            let ended = match ctx {
+                EventContext::Track(tracks) => current_track.as_ref().is_some_and(|current| {
+                    tracks
+                        .iter()
+                        .any(|(_, handle)| handle.uuid() == current.handle.uuid())
+                }),
+                _ => false,
+            };
+            if ended {
+                current_track.take()
+            } else {
+                None
+            }
+        };
+        if let Some(finished) = finished {
+            self.requeue(&mut queue, finished);
+        }
 
         let status = Arc::clone(&self.status);
         let mut status = status.lock().unwrap();
@@ -192,7 +1239,181 @@ impl VoiceEventHandler for Driver {
             self.notify.notify_one();
         } else if front.is_none() && (*status == Status::Playing || *status == Status::Paused) {
             *status = Status::Idle;
+
+            let mut pending = self.autoplay_pending.lock().unwrap();
+            if !*pending && self.autoplay_enabled() {
+                *pending = true;
+                // Looking up related tracks shells out to yt-dlp, keep it off
+                // the event handler
+                let driver = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = driver.autoplay_next().await {
+                        warn!("Autoplay failed: {e}");
+                    }
+                    *driver.autoplay_pending.lock().unwrap() = false;
+                });
+            }
         }
-        return None;
+        None
+    }
+}
+
+/// Watches the position of a playing track to preload the next one and to
+/// start the crossfade in time.
+struct TrackPosition {
+    driver: Driver,
+    duration: Duration,
+    crossfade: Duration,
+    preloading: AtomicBool,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackPosition {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track([(state, handle)]) = ctx else {
+            return None;
+        };
+
+        // Positions count played samples, which run faster or slower than
+        // the source under a speed effect
+        let speed = self.driver.effect().map_or(1.0, |effect| effect.speed());
+        let remaining = self.duration.div_f32(speed).saturating_sub(state.position);
+
+        if remaining <= self.crossfade + PRELOAD_LEAD
+            && !self.preloading.swap(true, Ordering::Relaxed)
+        {
+            let driver = self.driver.clone();
+            tokio::spawn(async move {
+                driver.preload().await;
+            });
+        }
+        if !self.crossfade.is_zero() && remaining <= self.crossfade {
+            self.driver.start_crossfade(handle);
+            return Some(Event::Cancel);
+        }
+        None
+    }
+}
+
+/// Brings the music back up once a piece of speech ends.
+struct Unduck {
+    driver: Driver,
+}
+
+#[async_trait]
+impl VoiceEventHandler for Unduck {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        self.driver.unduck();
+        None
+    }
+}
+
+/// Wakes whoever waits on `notify` when the track ends.
+struct TrackEnded {
+    notify: Arc<Notify>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackEnded {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        self.notify.notify_one();
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::speech::stub::SineStub;
+    use super::*;
+
+    #[tokio::test]
+    async fn ducks_music_while_speaking() {
+        let driver = Driver::new(
+            HttpClient::new(),
+            Settings::new(),
+            AudioCache::new(),
+            Arc::new(SineStub),
+        );
+        // Never connected, so nothing plays and the end of speech is
+        // signalled by hand
+        let call = Arc::new(tokio::sync::Mutex::new(Call::standalone(
+            GuildId::new(1),
+            UserId::new(2),
+        )));
+        driver.set_volume(0.8).unwrap();
+        let speech_ended = || async {
+            let unduck = Unduck {
+                driver: driver.clone(),
+            };
+            unduck.act(&EventContext::Track(&[])).await;
+        };
+
+        driver
+            .speak(Arc::clone(&call), "now playing")
+            .await
+            .unwrap();
+        driver.speak(Arc::clone(&call), "up next").await.unwrap();
+        assert_eq!(*driver.speaking.lock().unwrap(), 2);
+        assert_eq!(driver.level(), 0.8 * DUCK_LEVEL);
+
+        speech_ended().await;
+        assert_eq!(*driver.speaking.lock().unwrap(), 1);
+        assert_eq!(driver.level(), 0.8 * DUCK_LEVEL);
+
+        speech_ended().await;
+        assert_eq!(*driver.speaking.lock().unwrap(), 0);
+        assert_eq!(driver.level(), 0.8);
+
+        // An extra end never takes the volume below normal
+        speech_ended().await;
+        assert_eq!(*driver.speaking.lock().unwrap(), 0);
+        assert_eq!(driver.level(), 0.8);
+    }
+
+    fn request(user: u64, secs: Option<u64>) -> QueuedTrack {
+        let mut track = QueuedTrack::new(format!("https://youtu.be/{user}"), UserId::new(user));
+        track.duration = secs.map(Duration::from_secs);
+        track
+    }
+
+    #[test]
+    fn fair_mode_takes_turns() {
+        let mut queue = [1, 1, 1, 2, 2, 3]
+            .into_iter()
+            .map(|user| request(user, None))
+            .collect::<VecDeque<_>>();
+        let mut served = Vec::new();
+        let mut order = Vec::new();
+        while let Some(index) = fair_index(&queue, &served) {
+            let track = queue.remove(index).unwrap();
+            served.retain(|user| *user != track.requester);
+            served.push(track.requester);
+            order.push(track.requester.unwrap().get());
+        }
+        assert_eq!(order, [1, 2, 3, 1, 2, 1]);
+
+        // Whoever was served longest ago goes first
+        let queue = VecDeque::from([request(1, None), request(2, None)]);
+        let served = [Some(UserId::new(2)), Some(UserId::new(1))];
+        assert_eq!(fair_index(&queue, &served), Some(1));
+        assert_eq!(fair_index(&VecDeque::new(), &served), None);
+    }
+
+    #[test]
+    fn limits_length_and_tracks_per_user() {
+        let mut settings = GuildSettings::default();
+        let queue = VecDeque::from([request(1, Some(60)), request(1, Some(60))]);
+        assert!(check_limits(&settings, &queue, &request(1, None)).is_ok());
+
+        settings.max_track_length_secs = Some(300);
+        assert!(check_limits(&settings, &queue, &request(2, Some(300))).is_ok());
+        let error = check_limits(&settings, &queue, &request(2, Some(301))).unwrap_err();
+        assert!(error.to_string().contains("the limit is 5:00"));
+        let error = check_limits(&settings, &queue, &request(2, None)).unwrap_err();
+        assert!(error.to_string().starts_with("Can't tell how long"));
+
+        settings.max_tracks_per_user = Some(2);
+        assert!(check_limits(&settings, &queue, &request(1, Some(60))).is_err());
+        assert!(check_limits(&settings, &queue, &request(2, Some(60))).is_ok());
     }
 }
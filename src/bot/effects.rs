@@ -0,0 +1,396 @@
+use super::audio::{Biquad, Processor};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Centre frequencies of the `eq` bands, an octave apart.
+pub const EQ_BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+const EQ_MAX_GAIN_DB: f32 = 12.0;
+const SPEED_RANGE: (f32, f32) = (0.5, 2.0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    /// Gains in dB for the leading `EQ_BANDS`, the rest stay flat.
+    Eq(Vec<f32>),
+    /// Playback rate, pitch moves with it like a record played faster.
+    Speed(f32),
+}
+
+impl Effect {
+    /// How much faster than the source the effect plays.
+    pub fn speed(&self) -> f32 {
+        match self {
+            Effect::Nightcore => 1.25,
+            Effect::Vaporwave => 0.8,
+            Effect::Speed(factor) => *factor,
+            Effect::BassBoost | Effect::Eq(_) => 1.0,
+        }
+    }
+
+    /// Fresh DSP stages for this effect, state is per stream.
+    pub fn processors(&self) -> Vec<Box<dyn Processor>> {
+        match self {
+            Effect::BassBoost => vec![Box::new(Equalizer::new(vec![
+                Band::LowShelf(110.0, 9.0),
+                Band::Peaking(60.0, 3.0),
+            ]))],
+            Effect::Nightcore => vec![
+                Box::new(Resampler::new(1.25)),
+                Box::new(Equalizer::new(vec![Band::HighShelf(8000.0, 2.0)])),
+            ],
+            Effect::Vaporwave => vec![
+                Box::new(Resampler::new(0.8)),
+                Box::new(Equalizer::new(vec![Band::HighShelf(6000.0, -6.0)])),
+            ],
+            Effect::Eq(gains) => vec![Box::new(Equalizer::new(
+                EQ_BANDS
+                    .iter()
+                    .zip(gains)
+                    .filter(|(_, gain)| **gain != 0.0)
+                    .map(|(frequency, gain)| Band::Peaking(*frequency, *gain))
+                    .collect(),
+            ))],
+            Effect::Speed(factor) => vec![Box::new(Resampler::new(*factor))],
+        }
+    }
+}
+
+impl FromStr for Effect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = s.split_whitespace();
+        let name = args.next().unwrap_or_default().to_lowercase();
+        match name.as_str() {
+            "bassboost" | "bass" => Ok(Effect::BassBoost),
+            "nightcore" => Ok(Effect::Nightcore),
+            "vaporwave" => Ok(Effect::Vaporwave),
+            "eq" => {
+                let gains = args
+                    .map(|gain| gain.parse::<f32>().ok().filter(|g| g.abs() <= EQ_MAX_GAIN_DB))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|gains| !gains.is_empty() && gains.len() <= EQ_BANDS.len())
+                    .ok_or(format!(
+                        "Give up to {} band gains between -{EQ_MAX_GAIN_DB} and {EQ_MAX_GAIN_DB} dB, e.g. `eq 6 4 0 -2`",
+                        EQ_BANDS.len()
+                    ))?;
+                Ok(Effect::Eq(gains))
+            }
+            "speed" => args
+                .next()
+                .and_then(|factor| factor.trim_end_matches('x').parse::<f32>().ok())
+                .filter(|factor| (SPEED_RANGE.0..=SPEED_RANGE.1).contains(factor))
+                .map(Effect::Speed)
+                .ok_or(format!(
+                    "Speed must be between {} and {}",
+                    SPEED_RANGE.0, SPEED_RANGE.1
+                )),
+            _ => Err(format!(
+                "Unknown effect `{name}`, use bassboost, nightcore, vaporwave, eq or speed"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::BassBoost => write!(f, "bass boost"),
+            Effect::Nightcore => write!(f, "nightcore"),
+            Effect::Vaporwave => write!(f, "vaporwave"),
+            Effect::Eq(gains) => {
+                let gains = gains
+                    .iter()
+                    .map(|gain| gain.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "eq {}", gains.join(" "))
+            }
+            Effect::Speed(factor) => write!(f, "speed {factor}x"),
+        }
+    }
+}
+
+/// Runs whatever effect is currently selected for the guild, rebuilding the
+/// stages when it changes so `!fx` applies to the track already playing.
+pub struct EffectChain {
+    selected: Arc<Mutex<Option<Effect>>>,
+    active: Option<Effect>,
+    stages: Vec<Box<dyn Processor>>,
+}
+
+impl EffectChain {
+    pub fn new(selected: Arc<Mutex<Option<Effect>>>) -> Self {
+        Self {
+            selected,
+            active: None,
+            stages: Vec::new(),
+        }
+    }
+}
+
+impl Processor for EffectChain {
+    fn process(&mut self, samples: &mut Vec<f32>, sample_rate: u32, channels: usize) {
+        {
+            let selected = self.selected.lock().unwrap();
+            if *selected != self.active {
+                self.active = selected.clone();
+                self.stages = self
+                    .active
+                    .as_ref()
+                    .map(Effect::processors)
+                    .unwrap_or_default();
+            }
+        }
+        if self.stages.is_empty() {
+            return;
+        }
+
+        for stage in &mut self.stages {
+            stage.process(samples, sample_rate, channels);
+        }
+        // Boosts can push peaks past full scale, clip here rather than wrap
+        for sample in samples.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Band {
+    /// Corner frequency and gain in dB.
+    LowShelf(f32, f32),
+    HighShelf(f32, f32),
+    /// Centre frequency and gain in dB, one octave wide.
+    Peaking(f32, f32),
+}
+
+/// A set of biquad bands applied to every channel.
+struct Equalizer {
+    bands: Vec<Band>,
+    sample_rate: u32,
+    // One filter per band for each channel
+    filters: Vec<Vec<Biquad>>,
+}
+
+impl Equalizer {
+    fn new(bands: Vec<Band>) -> Self {
+        Self {
+            bands,
+            sample_rate: 0,
+            filters: Vec::new(),
+        }
+    }
+
+    fn build(&mut self, sample_rate: u32, channels: usize) {
+        let nyquist = sample_rate as f32 / 2.0;
+        let filters = self
+            .bands
+            .iter()
+            // Bands at or above Nyquist would make the filter unstable
+            .filter(|band| match band {
+                Band::LowShelf(f, _) | Band::HighShelf(f, _) | Band::Peaking(f, _) => {
+                    *f < nyquist * 0.9
+                }
+            })
+            .map(|band| match *band {
+                Band::LowShelf(f, gain) => Biquad::low_shelf(f, sample_rate, 0.707, gain),
+                Band::HighShelf(f, gain) => Biquad::high_shelf(f, sample_rate, 0.707, gain),
+                Band::Peaking(f, gain) => Biquad::peaking(f, sample_rate, 1.41, gain),
+            })
+            .collect::<Vec<_>>();
+        self.sample_rate = sample_rate;
+        self.filters = vec![filters; channels];
+    }
+}
+
+impl Processor for Equalizer {
+    fn process(&mut self, samples: &mut Vec<f32>, sample_rate: u32, channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        if self.sample_rate != sample_rate || self.filters.len() != channels {
+            self.build(sample_rate, channels);
+        }
+
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, filters) in frame.iter_mut().zip(self.filters.iter_mut()) {
+                *sample = filters
+                    .iter_mut()
+                    .fold(*sample, |sample, filter| filter.process(sample));
+            }
+        }
+    }
+}
+
+/// Plays the stream back `speed` times faster by linear interpolation, which
+/// shifts the pitch along with the tempo.
+struct Resampler {
+    speed: f64,
+    // Read position in frames, relative to `previous`
+    position: f64,
+    // Last frame of the previous block, so interpolation spans block edges
+    previous: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl Resampler {
+    fn new(speed: f32) -> Self {
+        Self {
+            speed: speed as f64,
+            position: 1.0,
+            previous: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Processor for Resampler {
+    fn process(&mut self, samples: &mut Vec<f32>, _sample_rate: u32, channels: usize) {
+        if channels == 0 || samples.len() < channels {
+            return;
+        }
+        if self.previous.len() != channels {
+            self.previous = vec![0.0; channels];
+        }
+
+        // Frame 0 is the carried over frame, the block follows it
+        let frames = samples.len() / channels;
+        let frame = |index: usize, channel: usize| match index {
+            0 => self.previous[channel],
+            _ => samples[(index - 1) * channels + channel],
+        };
+
+        self.output.clear();
+        while self.position < frames as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            for channel in 0..channels {
+                let a = frame(index, channel);
+                let b = frame(index + 1, channel);
+                self.output.push(a + (b - a) * fraction);
+            }
+            self.position += self.speed;
+        }
+
+        self.position -= frames as f64;
+        self.previous
+            .copy_from_slice(&samples[(frames - 1) * channels..frames * channels]);
+        std::mem::swap(samples, &mut self.output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 48_000;
+
+    /// One second of a stereo sine at `frequency` and half amplitude.
+    fn sine(frequency: f32) -> Vec<f32> {
+        (0..RATE)
+            .flat_map(|n| {
+                let sample = 0.5 * (2.0 * PI * frequency * n as f32 / RATE as f32).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    /// RMS of the left channel, skipping the first 100ms where filters settle.
+    fn rms(samples: &[f32]) -> f32 {
+        let left = samples
+            .chunks_exact(2)
+            .skip(RATE as usize / 10)
+            .map(|frame| frame[0])
+            .collect::<Vec<_>>();
+        (left.iter().map(|s| s * s).sum::<f32>() / left.len() as f32).sqrt()
+    }
+
+    fn gain_db(effect: &Effect, frequency: f32) -> f32 {
+        let input = sine(frequency);
+        let mut output = input.clone();
+        for mut stage in effect.processors() {
+            // Feed in blocks like the decoder does
+            let mut processed = Vec::new();
+            for block in output.chunks(2048) {
+                let mut block = block.to_vec();
+                stage.process(&mut block, RATE, 2);
+                processed.extend(block);
+            }
+            output = processed;
+        }
+        20.0 * (rms(&output) / rms(&input)).log10()
+    }
+
+    /// Frequency of the left channel estimated from upward zero crossings.
+    fn frequency(samples: &[f32], rate: u32) -> f32 {
+        let left = samples.chunks_exact(2).map(|f| f[0]).collect::<Vec<_>>();
+        let crossings = left
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count();
+        crossings as f32 * rate as f32 / left.len() as f32
+    }
+
+    #[test]
+    fn bass_boost_lifts_lows_only() {
+        assert!(gain_db(&Effect::BassBoost, 60.0) > 8.0);
+        assert!(gain_db(&Effect::BassBoost, 5000.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn eq_bands_follow_their_gains() {
+        let effect = "eq 0 0 0 0 0 -6 0 0 0 6".parse::<Effect>().unwrap();
+        assert!((gain_db(&effect, 1000.0) + 6.0).abs() < 0.5);
+        assert!(gain_db(&effect, 100.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn speed_shifts_pitch_and_length() {
+        let input = sine(440.0);
+        let mut output = Vec::new();
+        let mut resampler = Resampler::new(1.25);
+        for block in input.chunks(1000) {
+            let mut block = block.to_vec();
+            resampler.process(&mut block, RATE, 2);
+            assert_eq!(block.len() % 2, 0);
+            output.extend(block);
+        }
+
+        let frames = output.len() as f32 / 2.0;
+        assert!((frames - RATE as f32 / 1.25).abs() <= 2.0);
+        assert!((frequency(&output, RATE) - 550.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn chain_is_transparent_without_effect() {
+        let selected = Arc::new(Mutex::new(None));
+        let mut chain = EffectChain::new(Arc::clone(&selected));
+        let input = sine(440.0);
+
+        let mut samples = input.clone();
+        chain.process(&mut samples, RATE, 2);
+        assert_eq!(samples, input);
+
+        *selected.lock().unwrap() = Some(Effect::Speed(2.0));
+        chain.process(&mut samples, RATE, 2);
+        assert_eq!(samples.len(), input.len() / 2);
+    }
+
+    #[test]
+    fn parses_presets() {
+        assert_eq!("speed 1.5x".parse::<Effect>(), Ok(Effect::Speed(1.5)));
+        assert_eq!("Nightcore".parse::<Effect>(), Ok(Effect::Nightcore));
+        assert!("speed 4".parse::<Effect>().is_err());
+        assert!("eq 20".parse::<Effect>().is_err());
+        assert!("reverb".parse::<Effect>().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use super::bot::{Bot, Error};
+use super::soundboard::{self, BUTTON_PREFIX};
+use crate::tarkov::utils::{item_embed, task_embed, ITEM_MENU_ID, QUEST_MENU_ID};
+
+use poise::FrameworkContext;
+use serenity::all::{
+    ComponentInteraction, ComponentInteractionDataKind, Context as SerenityContext,
+    CreateInteractionResponse, CreateInteractionResponseMessage, FullEvent,
+};
+use tracing::info;
+
+/// Gateway events outside of commands. Components are routed by their
+/// custom id, so panels keep working across restarts.
+pub async fn handle(
+    ctx: &SerenityContext,
+    event: &FullEvent,
+    _framework: FrameworkContext<'_, Bot, Error>,
+    data: &Bot,
+) -> Result<(), Error> {
+    if let FullEvent::InteractionCreate { interaction } = event {
+        if let Some(component) = interaction.as_message_component() {
+            if let Some(name) = component.data.custom_id.strip_prefix(BUTTON_PREFIX) {
+                soundboard_button(ctx, component, data, name).await?;
+            } else if component.data.custom_id == QUEST_MENU_ID {
+                quest_choice(ctx, component, data).await?;
+            } else if component.data.custom_id == ITEM_MENU_ID {
+                item_choice(ctx, component, data).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn soundboard_button(
+    ctx: &SerenityContext,
+    component: &ComponentInteraction,
+    data: &Bot,
+    name: &str,
+) -> Result<(), Error> {
+    info!("SB BUTTON {name} pressed by {:?}", component.user.name);
+
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    // Custom ids come from the client, never trust them as file names
+    if soundboard::validate_name(name).is_err() {
+        return Ok(());
+    }
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Could not get songbird client")
+        .clone();
+
+    let response = match data
+        .play_clip(manager, guild_id, component.user.id, name)
+        .await
+    {
+        Ok(()) => CreateInteractionResponse::Acknowledge,
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(e.to_string())
+                .ephemeral(true),
+        ),
+    };
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+}
+
+/// Replaces the quest menu with the details of the picked quest.
+async fn quest_choice(
+    ctx: &SerenityContext,
+    component: &ComponentInteraction,
+    data: &Bot,
+) -> Result<(), Error> {
+    let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+        return Ok(());
+    };
+    let Some(id) = values.first() else {
+        return Ok(());
+    };
+    info!("QUEST MENU {id} picked by {:?}", component.user.name);
+
+    let response = match data.tarkov.task(id).await {
+        Ok(cached) => match Option::as_ref(&cached.value) {
+            Some(task) => CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(cached.note().unwrap_or_default())
+                    .embed(task_embed(task))
+                    .components(Vec::new()),
+            ),
+            None => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("tarkov.dev does not know that quest anymore")
+                    .ephemeral(true),
+            ),
+        },
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!("Could not fetch the quest: {e}"))
+                .ephemeral(true),
+        ),
+    };
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+}
+
+/// Replaces the item menu with the details of the picked item.
+async fn item_choice(
+    ctx: &SerenityContext,
+    component: &ComponentInteraction,
+    data: &Bot,
+) -> Result<(), Error> {
+    let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+        return Ok(());
+    };
+    let Some(id) = values.first() else {
+        return Ok(());
+    };
+    info!("ITEM MENU {id} picked by {:?}", component.user.name);
+
+    let response = match data.tarkov.item(id).await {
+        Ok(cached) => match Option::as_ref(&cached.value) {
+            Some(item) => CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(cached.note().unwrap_or_default())
+                    .embed(item_embed(item))
+                    .components(Vec::new()),
+            ),
+            None => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("tarkov.dev does not know that item anymore")
+                    .ephemeral(true),
+            ),
+        },
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!("Could not fetch the item: {e}"))
+                .ephemeral(true),
+        ),
+    };
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use super::track::QueuedTrack;
+
+use serenity::all::GuildId;
+use std::collections::{HashMap, VecDeque};
+use std::time::SystemTime;
+
+/// How many finished tracks are remembered per guild.
+const HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub track: QueuedTrack,
+    pub started_at: SystemTime,
+    pub skipped: bool,
+}
+
+/// Finished tracks per guild, most recent last.
+#[derive(Default)]
+pub struct History {
+    entries: HashMap<GuildId, VecDeque<HistoryEntry>>,
+}
+
+impl History {
+    pub fn push(&mut self, guild_id: GuildId, entry: HistoryEntry) {
+        let entries = self.entries.entry(guild_id).or_default();
+        entries.push_back(entry);
+        if entries.len() > HISTORY_LIMIT {
+            entries.pop_front();
+        }
+    }
+
+    pub fn pop_latest(&mut self, guild_id: GuildId) -> Option<HistoryEntry> {
+        self.entries.get_mut(&guild_id)?.pop_back()
+    }
+
+    /// Up to `count` entries, most recent first.
+    pub fn recent(&self, guild_id: GuildId, count: usize) -> Vec<HistoryEntry> {
+        self.entries
+            .get(&guild_id)
+            .map(|entries| entries.iter().rev().take(count).cloned().collect())
+            .unwrap_or_default()
+    }
+}
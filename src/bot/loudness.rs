@@ -0,0 +1,195 @@
+use super::audio::{Biquad, Processor};
+use super::bot::Error;
+use crate::storage::JsonStore;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const LOUDNESS_PATH: &str = "data/loudness.json";
+
+/// EBU R128 measures in 400ms blocks.
+const BLOCK_SECONDS: f32 = 0.4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Never boost or cut further than this, quiet intros would otherwise blast.
+const MIN_GAIN: f32 = 0.1;
+const MAX_GAIN: f32 = 2.0;
+
+/// Mean square energy of each finished block, shared between the meter
+/// running in the audio path and whoever reads the estimate.
+#[derive(Default)]
+pub struct LoudnessState {
+    blocks: Vec<f64>,
+}
+
+impl LoudnessState {
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Gated integrated loudness as in BS.1770, `None` until something audible played.
+    pub fn integrated_lufs(&self) -> Option<f64> {
+        let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let audible: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&energy| energy > 0.0 && loudness(energy) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if audible.is_empty() {
+            return None;
+        }
+
+        let threshold = loudness(mean(&audible)) + RELATIVE_GATE_LU;
+        let gated: Vec<f64> = audible
+            .into_iter()
+            .filter(|&energy| loudness(energy) > threshold)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+        Some(loudness(mean(&gated)))
+    }
+}
+
+/// Linear volume that moves `lufs` to `target_lufs`.
+pub fn gain_for(lufs: f64, target_lufs: f32) -> f32 {
+    let gain = 10f64.powf((target_lufs as f64 - lufs) / 20.0) as f32;
+    gain.clamp(MIN_GAIN, MAX_GAIN)
+}
+
+/// K-weighted loudness meter. Only measures, the samples pass through untouched.
+pub struct LoudnessMeter {
+    state: Arc<Mutex<LoudnessState>>,
+    filters: Vec<(Biquad, Biquad)>,
+    sample_rate: u32,
+    block_energy: f64,
+    block_frames: usize,
+}
+
+impl LoudnessMeter {
+    pub fn new(state: Arc<Mutex<LoudnessState>>) -> Self {
+        Self {
+            state,
+            filters: Vec::new(),
+            sample_rate: 0,
+            block_energy: 0.0,
+            block_frames: 0,
+        }
+    }
+
+    fn reset_filters(&mut self, sample_rate: u32, channels: usize) {
+        // Pre-filter and RLB high-pass from BS.1770
+        self.filters = (0..channels)
+            .map(|_| {
+                (
+                    Biquad::high_shelf(1681.97, sample_rate, 0.7072, 3.9998),
+                    Biquad::high_pass(38.135, sample_rate, 0.5003),
+                )
+            })
+            .collect();
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl Processor for LoudnessMeter {
+    fn process(&mut self, samples: &mut Vec<f32>, sample_rate: u32, channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        if self.sample_rate != sample_rate || self.filters.len() != channels {
+            self.reset_filters(sample_rate, channels);
+        }
+
+        let frames_per_block = (sample_rate as f32 * BLOCK_SECONDS) as usize;
+        for frame in samples.chunks_exact(channels) {
+            for (sample, (shelf, high_pass)) in frame.iter().zip(self.filters.iter_mut()) {
+                let weighted = high_pass.process(shelf.process(*sample)) as f64;
+                self.block_energy += weighted * weighted;
+            }
+            self.block_frames += 1;
+
+            if self.block_frames >= frames_per_block {
+                let energy = self.block_energy / self.block_frames as f64;
+                self.state.lock().unwrap().blocks.push(energy);
+                self.block_energy = 0.0;
+                self.block_frames = 0;
+            }
+        }
+    }
+}
+
+/// Loudness measured on earlier plays, keyed by `track_key`. The LUFS value
+/// is stored rather than the gain so a guild changing its target still
+/// gets the right level on replays.
+#[derive(Clone)]
+pub struct LoudnessCache {
+    store: JsonStore<HashMap<String, f64>>,
+}
+
+impl LoudnessCache {
+    pub fn new() -> Self {
+        Self {
+            store: JsonStore::new(LOUDNESS_PATH),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<f64> {
+        self.store.load().ok()?.get(key).copied()
+    }
+
+    pub fn insert(&self, key: String, lufs: f64) -> Result<(), Error> {
+        self.store.update(|measured| {
+            measured.insert(key, lufs);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean square energy of a block measuring `lufs`.
+    fn block(lufs: f64) -> f64 {
+        10f64.powf((lufs + 0.691) / 10.0)
+    }
+
+    fn integrated(blocks: Vec<f64>) -> Option<f64> {
+        LoudnessState { blocks }.integrated_lufs()
+    }
+
+    #[test]
+    fn constant_level_measures_itself() {
+        let lufs = integrated(vec![block(-20.0); 50]).unwrap();
+        assert!((lufs + 20.0).abs() < 1e-9, "{lufs}");
+    }
+
+    #[test]
+    fn gates_silence_and_quiet_blocks() {
+        let mut blocks = vec![block(-20.0); 20];
+        blocks.extend([0.0; 20]);
+        blocks.extend(vec![block(-80.0); 20]);
+        let lufs = integrated(blocks.clone()).unwrap();
+        assert!((lufs + 20.0).abs() < 1e-9, "{lufs}");
+
+        // More than 10 LU under the rest is left out too
+        blocks.extend(vec![block(-35.0); 20]);
+        let lufs = integrated(blocks).unwrap();
+        assert!((lufs + 20.0).abs() < 1e-9, "{lufs}");
+
+        assert_eq!(integrated(vec![0.0, block(-75.0)]), None);
+        assert_eq!(integrated(Vec::new()), None);
+    }
+
+    #[test]
+    fn clamps_gain() {
+        assert!((gain_for(-14.0, -14.0) - 1.0).abs() < 1e-6);
+        assert!((gain_for(-20.0, -14.0) - 1.995).abs() < 1e-3);
+        assert!((gain_for(0.0, -14.0) - 0.1995).abs() < 1e-4);
+        assert_eq!(gain_for(-40.0, -14.0), MAX_GAIN);
+        assert_eq!(gain_for(10.0, -14.0), MIN_GAIN);
+    }
+}
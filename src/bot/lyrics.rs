@@ -0,0 +1,350 @@
+use super::bot::{Context, Error};
+use super::track::{Answer, QueuedTrack};
+
+use poise::CreateReply;
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::Deserialize;
+use serenity::async_trait;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const LRCLIB_URL: &str = "https://lrclib.net";
+/// How often the synced display checks the track position.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+/// Discord throttles edits, the display never changes faster than this.
+const MIN_EDIT_INTERVAL: Duration = Duration::from_millis(1200);
+/// Lines shown before and after the current one.
+const LINES_BEFORE: usize = 2;
+const LINES_AFTER: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    pub at: Duration,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lyrics {
+    /// Lines with the time they are sung at, in order.
+    Synced(Vec<LyricLine>),
+    Plain(String),
+}
+
+/// Looks lyrics up by title and artist.
+#[async_trait]
+pub trait LyricsProvider: Send + Sync {
+    async fn lyrics(
+        &self,
+        title: &str,
+        artist: Option<&str>,
+        duration: Option<Duration>,
+    ) -> Result<Option<Lyrics>, Error>;
+}
+
+/// Lyrics of `track`, by the artist the title names or the uploader, then
+/// by title alone.
+pub async fn find(provider: &dyn LyricsProvider, track: &QueuedTrack) -> Result<Lyrics, Error> {
+    let answer = Answer::from_track(track).ok_or("The current track has no title to search for")?;
+    if let Some(artist) = &answer.artist {
+        if let Some(lyrics) = provider
+            .lyrics(&answer.title, Some(artist), track.duration)
+            .await?
+        {
+            return Ok(lyrics);
+        }
+    }
+    provider
+        .lyrics(&answer.title, None, track.duration)
+        .await?
+        .ok_or_else(|| format!("No lyrics found for {answer}").into())
+}
+
+/// The LRCLIB API, which serves synced lyrics for most releases.
+pub struct Lrclib {
+    http_client: HttpClient,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibRecord {
+    #[serde(default)]
+    instrumental: bool,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+impl Lrclib {
+    pub fn new(http_client: HttpClient) -> Self {
+        Self { http_client }
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for Lrclib {
+    async fn lyrics(
+        &self,
+        title: &str,
+        artist: Option<&str>,
+        duration: Option<Duration>,
+    ) -> Result<Option<Lyrics>, Error> {
+        // Exact lookups need the artist, search does without
+        let record = match artist {
+            Some(artist) => {
+                let mut query = vec![
+                    ("track_name", title.to_string()),
+                    ("artist_name", artist.to_string()),
+                ];
+                if let Some(duration) = duration {
+                    query.push(("duration", duration.as_secs().to_string()));
+                }
+                let response = self
+                    .http_client
+                    .get(format!("{LRCLIB_URL}/api/get"))
+                    .query(&query)
+                    .send()
+                    .await?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                Some(response.error_for_status()?.json::<LrclibRecord>().await?)
+            }
+            None => self
+                .http_client
+                .get(format!("{LRCLIB_URL}/api/search"))
+                .query(&[("q", title)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Vec<LrclibRecord>>()
+                .await?
+                .into_iter()
+                .next(),
+        };
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        if record.instrumental {
+            return Ok(Some(Lyrics::Plain("♪ Instrumental ♪".to_string())));
+        }
+        let synced = record.synced_lyrics.as_deref().map(parse_lrc);
+        Ok(match (synced, record.plain_lyrics) {
+            (Some(lines), _) if !lines.is_empty() => Some(Lyrics::Synced(lines)),
+            (_, Some(plain)) if !plain.trim().is_empty() => Some(Lyrics::Plain(plain)),
+            _ => None,
+        })
+    }
+}
+
+/// Parses `[mm:ss.xx] text` lines, which may carry several timestamps.
+/// Tags like `[ar:Artist]` and untimed lines are dropped.
+pub fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((stamp, after)) = tag.split_once(']') else {
+                break;
+            };
+            match parse_timestamp(stamp) {
+                Some(at) => times.push(at),
+                None => break,
+            }
+            rest = after.trim_start();
+        }
+        for at in times {
+            lines.push(LyricLine {
+                at,
+                text: rest.trim().to_string(),
+            });
+        }
+    }
+    lines.sort_by_key(|line| line.at);
+    lines
+}
+
+fn parse_timestamp(stamp: &str) -> Option<Duration> {
+    let (minutes, seconds) = stamp.split_once(':')?;
+    let minutes = minutes.parse::<u64>().ok()?;
+    let seconds = seconds.parse::<f64>().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// Index of the line being sung at `position`, `None` before the first.
+pub fn line_at(lines: &[LyricLine], position: Duration) -> Option<usize> {
+    lines
+        .partition_point(|line| line.at <= position)
+        .checked_sub(1)
+}
+
+/// The lines around `current`, with the current one highlighted.
+pub fn render_window(lines: &[LyricLine], current: Option<usize>) -> String {
+    let center = current.unwrap_or(0);
+    let start = center.saturating_sub(LINES_BEFORE);
+    let end = (center + LINES_AFTER + 1).min(lines.len());
+    lines[start..end]
+        .iter()
+        .enumerate()
+        .map(|(offset, line)| {
+            let text = match line.text.as_str() {
+                "" => "♪",
+                text => text,
+            };
+            if Some(start + offset) == current {
+                format!("**▶ {text}**")
+            } else {
+                text.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Posts the lyrics around the current line and keeps the message in step
+/// with the track until it ends or another one starts.
+pub async fn follow(
+    ctx: Context<'_>,
+    track: &QueuedTrack,
+    lines: &[LyricLine],
+) -> Result<(), Error> {
+    let header = format!("🎤 **{}**", track.display_title());
+    let position = match ctx.data.driver.now_playing().await {
+        Some((_, position)) => position,
+        None => return Ok(()),
+    };
+    let mut current = line_at(lines, position);
+    let message = ctx
+        .say(format!("{header}\n{}", render_window(lines, current)))
+        .await?;
+    let mut last_edit = Instant::now();
+
+    let mut interval = tokio::time::interval(FOLLOW_INTERVAL);
+    loop {
+        interval.tick().await;
+        let position = match ctx.data.driver.now_playing().await {
+            Some((playing, position)) if playing.url == track.url => position,
+            _ => break,
+        };
+        let line = line_at(lines, position);
+        if line == current || last_edit.elapsed() < MIN_EDIT_INTERVAL {
+            continue;
+        }
+        current = line;
+        message
+            .edit(
+                poise::Context::Prefix(ctx),
+                CreateReply::default()
+                    .content(format!("{header}\n{}", render_window(lines, current))),
+            )
+            .await?;
+        last_edit = Instant::now();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const LRC: &str = "[ar:Rick Astley]\n[ti:Never Gonna Give You Up]\n[00:18.50]We're no strangers to love\n[00:22.10]You know the rules and so do I\n[00:43.00][01:50.25]Never gonna give you up\n[00:45.00]\n";
+
+    /// Lyrics keyed by title and artist, as a provider would have them.
+    struct StubProvider {
+        entries: HashMap<(String, Option<String>), Lyrics>,
+    }
+
+    #[async_trait]
+    impl LyricsProvider for StubProvider {
+        async fn lyrics(
+            &self,
+            title: &str,
+            artist: Option<&str>,
+            _duration: Option<Duration>,
+        ) -> Result<Option<Lyrics>, Error> {
+            let key = (title.to_string(), artist.map(str::to_string));
+            Ok(self.entries.get(&key).cloned())
+        }
+    }
+
+    fn track(title: &str, artist: Option<&str>) -> QueuedTrack {
+        QueuedTrack {
+            title: Some(title.to_string()),
+            artist: artist.map(str::to_string),
+            ..QueuedTrack::autoplay("https://youtu.be/dQw4w9WgXcQ".to_string())
+        }
+    }
+
+    #[test]
+    fn parses_lrc() {
+        let lines = parse_lrc(LRC);
+        let times = lines.iter().map(|line| line.at).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                Duration::from_millis(18_500),
+                Duration::from_millis(22_100),
+                Duration::from_secs(43),
+                Duration::from_secs(45),
+                Duration::from_millis(110_250),
+            ]
+        );
+        assert_eq!(lines[2].text, "Never gonna give you up");
+        assert_eq!(lines[4].text, "Never gonna give you up");
+        assert_eq!(lines[3].text, "");
+    }
+
+    #[test]
+    fn finds_line_at_position() {
+        let lines = parse_lrc(LRC);
+        assert_eq!(line_at(&lines, Duration::from_secs(5)), None);
+        assert_eq!(line_at(&lines, Duration::from_millis(18_500)), Some(0));
+        assert_eq!(line_at(&lines, Duration::from_secs(30)), Some(1));
+        assert_eq!(line_at(&lines, Duration::from_secs(200)), Some(4));
+    }
+
+    #[test]
+    fn highlights_current_line() {
+        let lines = parse_lrc(LRC);
+        let window = render_window(&lines, Some(1));
+        assert!(window.starts_with("We're no strangers to love\n"));
+        assert!(window.contains("**▶ You know the rules and so do I**"));
+        assert!(window.contains("\n♪\n"));
+    }
+
+    #[tokio::test]
+    async fn finds_by_artist_from_title() {
+        let synced = Lyrics::Synced(parse_lrc(LRC));
+        let provider = StubProvider {
+            entries: HashMap::from([(
+                (
+                    "Never Gonna Give You Up".to_string(),
+                    Some("Rick Astley".to_string()),
+                ),
+                synced.clone(),
+            )]),
+        };
+        let track = track(
+            "Rick Astley - Never Gonna Give You Up (Official Music Video)",
+            Some("Rick Astley"),
+        );
+        assert_eq!(find(&provider, &track).await.unwrap(), synced);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_title_only() {
+        let plain = Lyrics::Plain("Some lyrics".to_string());
+        let provider = StubProvider {
+            entries: HashMap::from([(("Song".to_string(), None), plain.clone())]),
+        };
+        let track = track("Song", Some("Uploader - Topic"));
+        assert_eq!(find(&provider, &track).await.unwrap(), plain);
+
+        let missing = self::track("Other song", None);
+        assert!(find(&provider, &missing).await.is_err());
+    }
+}
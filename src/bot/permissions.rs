@@ -0,0 +1,133 @@
+use super::bot::{Bot, Error};
+use super::settings::GuildSettings;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{Guild, Member};
+use std::fmt;
+use std::str::FromStr;
+use tracing::info;
+
+/// Role name used as the DJ role until a guild configures one with `!perm dj`.
+pub const DJ_ROLE: &str = "DJ";
+
+/// Who may run a command. Ordered so that a higher level includes the lower ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Access {
+    Anyone,
+    Dj,
+    Admin,
+}
+
+impl Access {
+    /// Policy for commands a guild has not configured.
+    pub fn default_for(command: &str) -> Access {
+        match command {
+            "pause" | "leave" | "volume" | "loop" | "resume-session" | "autoplay" | "normalize"
+            | "fx" | "crossfade" | "say" | "announce" | "back" | "replay" => Access::Dj,
+            "voteskip" | "perm" | "limits" | "fair" | "record" => Access::Admin,
+            _ => Access::Anyone,
+        }
+    }
+}
+
+impl FromStr for Access {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "anyone" | "everyone" => Ok(Access::Anyone),
+            "dj" => Ok(Access::Dj),
+            "admin" => Ok(Access::Admin),
+            _ => Err(format!(
+                "Unknown access level `{s}`, use anyone, dj or admin"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Anyone => write!(f, "anyone"),
+            Access::Dj => write!(f, "dj"),
+            Access::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+pub fn required_access(settings: &GuildSettings, command: &str) -> Access {
+    settings
+        .permissions
+        .get(command)
+        .copied()
+        .unwrap_or_else(|| Access::default_for(command))
+}
+
+pub fn member_access(guild: &Guild, member: &Member, settings: &GuildSettings) -> Access {
+    let permissions = guild.member_permissions(member);
+    if permissions.administrator() || permissions.manage_guild() {
+        return Access::Admin;
+    }
+
+    let is_dj = member.roles.iter().any(|role_id| match settings.dj_role {
+        Some(dj_role) => *role_id == dj_role,
+        None => guild
+            .roles
+            .get(role_id)
+            .is_some_and(|role| role.name.eq_ignore_ascii_case(DJ_ROLE)),
+    });
+    if is_dj {
+        Access::Dj
+    } else {
+        Access::Anyone
+    }
+}
+
+/// Access level of whoever invoked the command. Owners are always admins.
+pub async fn author_access(ctx: poise::Context<'_, Bot, Error>) -> Access {
+    if ctx.framework().options().owners.contains(&ctx.author().id) {
+        return Access::Admin;
+    }
+    let (Some(guild_id), Some(member)) = (ctx.guild_id(), ctx.author_member().await) else {
+        return Access::Anyone;
+    };
+
+    let settings = ctx.data().settings.get(guild_id);
+    match ctx.guild() {
+        Some(guild) => member_access(&guild, &member, &settings),
+        None => Access::Anyone,
+    }
+}
+
+/// Command check enforcing the guild's permission policy.
+pub async fn check(ctx: poise::Context<'_, Bot, Error>) -> Result<bool, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+
+    // Subcommands share the policy of their parent command
+    let command = ctx
+        .command()
+        .qualified_name
+        .split(' ')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let required = required_access(&ctx.data().settings.get(guild_id), &command);
+    if required == Access::Anyone {
+        return Ok(true);
+    }
+
+    let access = author_access(ctx).await;
+    if access >= required {
+        return Ok(true);
+    }
+
+    info!(
+        "{:?} denied !{command}, needs {required} but has {access}",
+        ctx.author().name
+    );
+    ctx.say(format!("You need {required} access to use !{command}"))
+        .await?;
+    Ok(false)
+}
@@ -0,0 +1,133 @@
+pub mod formats;
+
+use super::bot::Error;
+use super::track::QueuedTrack;
+use crate::storage::JsonStore;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{GuildId, UserId};
+use std::collections::{BTreeMap, HashMap};
+
+const PLAYLISTS_PATH: &str = "data/playlists.json";
+
+/// Whose playlist this is: one user's, or shared with everyone in a guild.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    User(UserId),
+    Guild(GuildId),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    pub owner: UserId,
+    pub tracks: Vec<QueuedTrack>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PlaylistData {
+    users: HashMap<u64, BTreeMap<String, Playlist>>,
+    guilds: HashMap<u64, BTreeMap<String, Playlist>>,
+}
+
+impl PlaylistData {
+    fn scope(&mut self, scope: Scope) -> &mut BTreeMap<String, Playlist> {
+        match scope {
+            Scope::User(user_id) => self.users.entry(user_id.get()).or_default(),
+            Scope::Guild(guild_id) => self.guilds.entry(guild_id.get()).or_default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Playlists {
+    store: JsonStore<PlaylistData>,
+}
+
+impl Playlists {
+    pub fn new() -> Self {
+        Self {
+            store: JsonStore::new(PLAYLISTS_PATH),
+        }
+    }
+
+    pub fn get(&self, scope: Scope, name: &str) -> Result<Option<Playlist>, Error> {
+        let mut data = self.store.load()?;
+        Ok(data.scope(scope).remove(&key(name)))
+    }
+
+    /// Looks the name up in the user's own playlists first, then the guild's.
+    pub fn find(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        name: &str,
+    ) -> Result<Option<(Scope, Playlist)>, Error> {
+        for scope in [Scope::User(user_id), Scope::Guild(guild_id)] {
+            if let Some(playlist) = self.get(scope, name)? {
+                return Ok(Some((scope, playlist)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn list(&self, scope: Scope) -> Result<Vec<Playlist>, Error> {
+        let mut data = self.store.load()?;
+        Ok(data.scope(scope).values().cloned().collect())
+    }
+
+    /// Creates or replaces a playlist.
+    pub fn save(&self, scope: Scope, playlist: Playlist) -> Result<(), Error> {
+        self.store.update(|data| {
+            data.scope(scope).insert(key(&playlist.name), playlist);
+        })
+    }
+
+    /// Appends a track, creating the playlist if it doesn't exist yet.
+    pub fn add(
+        &self,
+        scope: Scope,
+        name: &str,
+        owner: UserId,
+        track: QueuedTrack,
+    ) -> Result<usize, Error> {
+        self.store.update(|data| {
+            let playlist = data.scope(scope).entry(key(name)).or_insert(Playlist {
+                name: name.to_string(),
+                owner,
+                tracks: Vec::new(),
+            });
+            playlist.tracks.push(track);
+            playlist.tracks.len()
+        })
+    }
+
+    /// Removes the entry at `index` (0-based) and returns it.
+    pub fn remove(&self, scope: Scope, name: &str, index: usize) -> Result<QueuedTrack, Error> {
+        self.store
+            .update(|data| {
+                let playlist = data
+                    .scope(scope)
+                    .get_mut(&key(name))
+                    .ok_or("There is no playlist with that name")?;
+                if index >= playlist.tracks.len() {
+                    return Err(format!(
+                        "{} only has {} tracks",
+                        playlist.name,
+                        playlist.tracks.len()
+                    ));
+                }
+                Ok(playlist.tracks.remove(index))
+            })?
+            .map_err(Into::into)
+    }
+
+    pub fn delete(&self, scope: Scope, name: &str) -> Result<Option<Playlist>, Error> {
+        self.store
+            .update(|data| data.scope(scope).remove(&key(name)))
+    }
+}
+
+fn key(name: &str) -> String {
+    name.to_lowercase()
+}
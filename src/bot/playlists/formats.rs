@@ -0,0 +1,319 @@
+use super::super::bot::Error;
+use super::super::track::QueuedTrack;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+static XSPF_TRACK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<track>(.*?)</track>").expect("XSPF regex failed to compile"));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    M3u,
+    Xspf,
+    Json,
+}
+
+impl Format {
+    pub fn from_filename(filename: &str) -> Option<Format> {
+        let (_, extension) = filename.rsplit_once('.')?;
+        extension.parse().ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::M3u => "m3u8",
+            Format::Xspf => "xspf",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Ok(Format::M3u),
+            "xspf" => Ok(Format::Xspf),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "Unknown playlist format `{s}`, use m3u8, xspf or json"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPlaylist {
+    #[serde(default)]
+    name: String,
+    tracks: Vec<JsonTrack>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTrack {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    artist: Option<String>,
+    #[serde(default)]
+    duration_secs: Option<u64>,
+}
+
+pub fn export(format: Format, name: &str, tracks: &[QueuedTrack]) -> Result<String, Error> {
+    match format {
+        Format::M3u => {
+            let mut out = format!("#EXTM3U\n#PLAYLIST:{name}\n");
+            for track in tracks {
+                let duration = track.duration.map_or(-1, |d| d.as_secs() as i64);
+                out.push_str(&format!(
+                    "#EXTINF:{duration},{}\n{}\n",
+                    label(track),
+                    track.url
+                ));
+            }
+            Ok(out)
+        }
+        Format::Xspf => {
+            let mut out = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  \
+                <title>{}</title>\n  <trackList>\n",
+                escape_xml(name)
+            );
+            for track in tracks {
+                out.push_str("    <track>\n");
+                out.push_str(&format!(
+                    "      <location>{}</location>\n",
+                    escape_xml(&track.url)
+                ));
+                if let Some(title) = &track.title {
+                    out.push_str(&format!("      <title>{}</title>\n", escape_xml(title)));
+                }
+                if let Some(artist) = &track.artist {
+                    out.push_str(&format!(
+                        "      <creator>{}</creator>\n",
+                        escape_xml(artist)
+                    ));
+                }
+                if let Some(duration) = track.duration {
+                    out.push_str(&format!(
+                        "      <duration>{}</duration>\n",
+                        duration.as_millis()
+                    ));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+            Ok(out)
+        }
+        Format::Json => {
+            let playlist = JsonPlaylist {
+                name: name.to_string(),
+                tracks: tracks
+                    .iter()
+                    .map(|track| JsonTrack {
+                        url: Some(track.url.clone()),
+                        title: track.title.clone(),
+                        artist: track.artist.clone(),
+                        duration_secs: track.duration.map(|d| d.as_secs()),
+                    })
+                    .collect(),
+            };
+            Ok(serde_json::to_string_pretty(&playlist)?)
+        }
+    }
+}
+
+/// What to hand to the providers for each entry: a link when the file has
+/// one, otherwise an "artist - title" search query.
+pub fn import(format: Format, content: &str) -> Result<Vec<String>, Error> {
+    let entries = match format {
+        Format::M3u => {
+            let mut entries = Vec::new();
+            let mut extinf: Option<String> = None;
+            for line in content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+            {
+                if let Some(info) = line.strip_prefix("#EXTINF:") {
+                    extinf = info
+                        .split_once(',')
+                        .map(|(_, title)| title.trim().to_string());
+                } else if !line.starts_with('#') {
+                    // Local file paths are useless to us, search for the title instead
+                    let entry = match extinf.take() {
+                        Some(title) if !is_link(line) && !title.is_empty() => title,
+                        _ => line.to_string(),
+                    };
+                    entries.push(entry);
+                }
+            }
+            entries
+        }
+        Format::Xspf => XSPF_TRACK
+            .captures_iter(content)
+            .filter_map(|captures| {
+                let track = &captures[1];
+                let location = xml_tag(track, "location").filter(|l| is_link(l));
+                let title = xml_tag(track, "title");
+                let creator = xml_tag(track, "creator");
+                location.or(match (creator, title) {
+                    (Some(creator), Some(title)) => Some(format!("{creator} - {title}")),
+                    (None, Some(title)) => Some(title),
+                    _ => None,
+                })
+            })
+            .collect(),
+        Format::Json => {
+            let playlist: JsonPlaylist = serde_json::from_str(content)?;
+            playlist
+                .tracks
+                .into_iter()
+                .filter_map(|track| {
+                    let query = match (track.artist, track.title) {
+                        (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+                        (_, title) => title,
+                    };
+                    track.url.filter(|url| is_link(url)).or(query)
+                })
+                .collect()
+        }
+    };
+    Ok(entries)
+}
+
+/// File name for an export of `name`, keeping only letters, digits, `-`
+/// and `_` so a playlist name can't reach outside the attachment name.
+pub fn filename(format: Format, name: &str) -> String {
+    let stem = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect::<String>();
+    let stem = if stem.is_empty() { "playlist" } else { &stem };
+    format!("{stem}.{}", format.extension())
+}
+
+fn label(track: &QueuedTrack) -> String {
+    match (&track.artist, &track.title) {
+        (Some(artist), Some(title)) => format!("{artist} - {title}"),
+        _ => track.display_title().to_string(),
+    }
+}
+
+fn is_link(entry: &str) -> bool {
+    entry.starts_with("http://") || entry.starts_with("https://")
+}
+
+fn xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    let value = unescape_xml(xml[start..end].trim());
+    (!value.is_empty()).then_some(value)
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn tracks() -> Vec<QueuedTrack> {
+        vec![
+            QueuedTrack {
+                url: "https://youtu.be/a?b=1&c=2".to_string(),
+                requester: None,
+                title: Some("Tom & Jerry <live>".to_string()),
+                artist: Some("Band".to_string()),
+                duration: Some(Duration::from_secs(201)),
+                autoplay: false,
+            },
+            QueuedTrack {
+                url: "https://soundcloud.com/x/y".to_string(),
+                requester: None,
+                title: None,
+                artist: None,
+                duration: None,
+                autoplay: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trips_links() {
+        let tracks = tracks();
+        for format in [Format::M3u, Format::Xspf, Format::Json] {
+            let content = export(format, "mix", &tracks).unwrap();
+            assert_eq!(
+                import(format, &content).unwrap(),
+                ["https://youtu.be/a?b=1&c=2", "https://soundcloud.com/x/y"],
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn searches_entries_without_links() {
+        let m3u = "#EXTM3U\n#EXTINF:200,Band - Song\nC:\\Music\\song.mp3\nfile.mp3\n";
+        assert_eq!(
+            import(Format::M3u, m3u).unwrap(),
+            ["Band - Song", "file.mp3"]
+        );
+
+        let xspf = "<trackList><track><location>file:///song.mp3</location>\
+            <title>Song &amp; more</title><creator>Band</creator></track>\
+            <track><title>Only title</title></track><track></track></trackList>";
+        assert_eq!(
+            import(Format::Xspf, xspf).unwrap(),
+            ["Band - Song & more", "Only title"]
+        );
+
+        let json = r#"{"tracks": [{"title": "Song", "artist": "Band"}, {"url": "song.mp3"}]}"#;
+        assert_eq!(import(Format::Json, json).unwrap(), ["Band - Song"]);
+    }
+
+    #[test]
+    fn handles_malformed_input() {
+        assert!(import(Format::Json, "{not json").is_err());
+        assert!(import(Format::Json, r#"{"name": "no tracks"}"#).is_err());
+        assert!(import(Format::M3u, "#EXTM3U\n#EXTINF:oops\n")
+            .unwrap()
+            .is_empty());
+        assert!(import(Format::Xspf, "<track><title>unclosed")
+            .unwrap()
+            .is_empty());
+        assert!(import(Format::Xspf, "<track><title>Open</track>")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn filename_keeps_safe_characters() {
+        assert_eq!(filename(Format::M3u, "road_trip-2"), "road_trip-2.m3u8");
+        assert_eq!(filename(Format::Json, "../../etc/passwd"), "etcpasswd.json");
+        assert_eq!(filename(Format::Xspf, "日本"), "playlist.xspf");
+    }
+}
@@ -8,7 +8,7 @@ use crate::bot::providers::youtube::YouTubeProvider;
 
 use reqwest::Client as HttpClient;
 use serenity::async_trait;
-use songbird::input::Input;
+use songbird::input::{AuxMetadata, Input};
 
 pub enum Providers {
     YouTube(YouTubeProvider),
@@ -23,6 +23,24 @@ impl Providers {
         ]
     }
 
+    /// Builds the stream for an already resolved track URL, falling back to
+    /// the first provider when none claims it (yt-dlp handles most links).
+    pub fn stream_for(http_client: HttpClient, url: String) -> Input {
+        let providers = Providers::all();
+        let provider = providers
+            .iter()
+            .find(|p| p.is_valid(&url))
+            .unwrap_or(&providers[0]);
+        provider.get_stream(http_client, url)
+    }
+
+    /// Title, artist and duration of a resolved track URL.
+    pub async fn metadata(http_client: HttpClient, url: String) -> Result<AuxMetadata, Error> {
+        Ok(Providers::stream_for(http_client, url)
+            .aux_metadata()
+            .await?)
+    }
+
     pub fn get_stream(&self, http_client: HttpClient, url: String) -> Input {
         match self {
             Providers::YouTube(p) => p.get_stream(http_client, url),
@@ -0,0 +1,365 @@
+use super::bot::{Context, Error};
+use super::providers::Providers;
+use super::track::{Answer, QueuedTrack};
+use crate::text::edit_distance;
+
+use poise::futures_util::StreamExt;
+use poise::CreateReply;
+use serenity::all::{CreateAllowedMentions, UserId};
+use serenity::collector::MessageCollector;
+use serenity::model::mention::Mentionable;
+use songbird::Songbird;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// How much of each track is played per round.
+pub const SNIPPET_LENGTH: Duration = Duration::from_secs(15);
+/// Time to guess before the answer is revealed, counted from the snippet start.
+pub const ROUND_TIME: Duration = Duration::from_secs(30);
+pub const DEFAULT_ROUNDS: usize = 10;
+pub const MAX_ROUNDS: usize = 25;
+const TITLE_POINTS: u32 = 2;
+const ARTIST_POINTS: u32 = 1;
+/// Share of a guess that has to be right, so typos still count.
+const MATCH_THRESHOLD: f32 = 0.8;
+/// Snippets start at least this far from either end of the track.
+const SNIPPET_MARGIN: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Title,
+    Artist,
+}
+
+impl Part {
+    pub fn points(self) -> u32 {
+        match self {
+            Part::Title => TITLE_POINTS,
+            Part::Artist => ARTIST_POINTS,
+        }
+    }
+}
+
+/// One track being guessed. The title and the artist each go to whoever
+/// names them first.
+pub struct Round {
+    pub answer: Answer,
+    title_by: Option<UserId>,
+    artist_by: Option<UserId>,
+}
+
+impl Round {
+    pub fn new(answer: Answer) -> Self {
+        Self {
+            answer,
+            title_by: None,
+            artist_by: None,
+        }
+    }
+
+    /// Credits `user` with the parts of the answer `guess` names that
+    /// nobody got yet.
+    pub fn guess(&mut self, user: UserId, guess: &str) -> Vec<Part> {
+        let mut credited = Vec::new();
+        if self.title_by.is_none() && matches(guess, &self.answer.title) {
+            self.title_by = Some(user);
+            credited.push(Part::Title);
+        }
+        if let Some(artist) = &self.answer.artist {
+            if self.artist_by.is_none() && matches(guess, artist) {
+                self.artist_by = Some(user);
+                credited.push(Part::Artist);
+            }
+        }
+        credited
+    }
+
+    pub fn solved(&self) -> bool {
+        self.title_by.is_some() && (self.answer.artist.is_none() || self.artist_by.is_some())
+    }
+}
+
+#[derive(Default)]
+pub struct Scoreboard {
+    scores: HashMap<UserId, u32>,
+}
+
+impl Scoreboard {
+    pub fn add(&mut self, user: UserId, points: u32) {
+        *self.scores.entry(user).or_default() += points;
+    }
+
+    /// Players from highest to lowest score.
+    pub fn ranked(&self) -> Vec<(UserId, u32)> {
+        let mut ranked = self
+            .scores
+            .iter()
+            .map(|(user, score)| (*user, *score))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+
+    pub fn render(&self) -> String {
+        let ranked = self.ranked();
+        if ranked.is_empty() {
+            return "Nobody has scored yet".to_string();
+        }
+        ranked
+            .iter()
+            .enumerate()
+            .map(|(rank, (user, score))| {
+                let place = match rank {
+                    0 => "🥇".to_string(),
+                    1 => "🥈".to_string(),
+                    2 => "🥉".to_string(),
+                    _ => format!("{}.", rank + 1),
+                };
+                format!("{place} {} {score}", user.mention())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The running game, there is one driver so there is at most one quiz.
+#[derive(Clone, Default)]
+pub struct Quiz {
+    stop: Arc<Mutex<Option<Arc<Notify>>>>,
+}
+
+impl Quiz {
+    /// Claims the quiz slot, returning what `stop` will signal.
+    pub fn begin(&self) -> Result<Arc<Notify>, Error> {
+        let mut stop = self.stop.lock().unwrap();
+        if stop.is_some() {
+            return Err("A quiz is already running, use !quiz stop to end it".into());
+        }
+        let notify = Arc::new(Notify::new());
+        *stop = Some(Arc::clone(&notify));
+        Ok(notify)
+    }
+
+    /// Asks the running quiz to end, false when there is none.
+    pub fn stop(&self) -> bool {
+        match &*self.stop.lock().unwrap() {
+            Some(notify) => {
+                notify.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn end(&self) {
+        *self.stop.lock().unwrap() = None;
+    }
+}
+
+/// Plays `rounds` snippets from `tracks` in random order, collecting guesses
+/// from the channel the quiz was started in, until done or stopped.
+pub async fn run(
+    ctx: Context<'_>,
+    manager: Arc<Songbird>,
+    mut tracks: Vec<QueuedTrack>,
+    rounds: usize,
+    stop: Arc<Notify>,
+) -> Result<(), Error> {
+    tracks.sort_by_cached_key(|_| random_below(u64::MAX));
+    let mut scoreboard = Scoreboard::default();
+    let mut played = 0;
+    let mut stopped = false;
+
+    for mut track in tracks {
+        if played == rounds || stopped {
+            break;
+        }
+        if track.title.is_none() {
+            match Providers::metadata(ctx.data.http_client.clone(), track.url.clone()).await {
+                Ok(metadata) => track = track.with_metadata(&metadata),
+                Err(e) => warn!("Could not fetch quiz track metadata: {e}"),
+            }
+        }
+        let Some(answer) = Answer::from_track(&track) else {
+            continue;
+        };
+        played += 1;
+
+        let handle = ctx
+            .data
+            .driver
+            .play_snippet(manager.clone(), &track, snippet_offset(track.duration))
+            .await?;
+        let board = ctx
+            .send(
+                CreateReply::default()
+                    .content(board_text(played, rounds, &scoreboard))
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+
+        let mut round = Round::new(answer);
+        let mut guesses = MessageCollector::new(ctx.serenity_context())
+            .channel_id(ctx.channel_id())
+            .timeout(ROUND_TIME)
+            .stream();
+        let snippet_end = Instant::now() + SNIPPET_LENGTH;
+        let mut snippet_over = false;
+        loop {
+            tokio::select! {
+                message = guesses.next() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    // Commands like !quiz stop are not guesses
+                    if message.author.bot || message.content.starts_with('!') {
+                        continue;
+                    }
+                    let credited = round.guess(message.author.id, &message.content);
+                    if credited.is_empty() {
+                        continue;
+                    }
+                    for part in credited {
+                        scoreboard.add(message.author.id, part.points());
+                    }
+                    let _ = message.react(&ctx.http(), '✅').await;
+                    board
+                        .edit(
+                            poise::Context::Prefix(ctx),
+                            CreateReply::default()
+                                .content(board_text(played, rounds, &scoreboard))
+                                .allowed_mentions(CreateAllowedMentions::new()),
+                        )
+                        .await?;
+                    if round.solved() {
+                        break;
+                    }
+                }
+                _ = stop.notified() => {
+                    stopped = true;
+                    break;
+                }
+                _ = tokio::time::sleep_until(snippet_end), if !snippet_over => {
+                    let _ = handle.stop();
+                    snippet_over = true;
+                }
+            }
+        }
+        let _ = handle.stop();
+        ctx.say(format!("It was {}", round.answer)).await?;
+    }
+
+    if played == 0 {
+        return Err("None of those tracks have a title to guess".into());
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "🏁 **Quiz over** after {played} rounds\n{}",
+                scoreboard.render()
+            ))
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+fn board_text(round: usize, rounds: usize, scoreboard: &Scoreboard) -> String {
+    format!(
+        "🎵 **Round {round}/{rounds}**, name the title ({TITLE_POINTS} points) or the artist ({ARTIST_POINTS} point) within {} seconds\n{}",
+        ROUND_TIME.as_secs(),
+        scoreboard.render()
+    )
+}
+
+/// Somewhere in the middle of the track, the start when its length is unknown.
+fn snippet_offset(duration: Option<Duration>) -> Duration {
+    let Some(duration) = duration else {
+        return Duration::ZERO;
+    };
+    let span = duration.saturating_sub(SNIPPET_LENGTH + SNIPPET_MARGIN * 2);
+    if span.is_zero() {
+        return Duration::ZERO;
+    }
+    SNIPPET_MARGIN + Duration::from_secs(random_below(span.as_secs().max(1)))
+}
+
+/// Every `RandomState` is seeded differently, which is all the randomness
+/// shuffling a quiz needs.
+fn random_below(bound: u64) -> u64 {
+    RandomState::new().build_hasher().finish() % bound.max(1)
+}
+
+/// Whether `guess` names `answer`, allowing for case, punctuation, extra
+/// words around it and small typos.
+pub fn matches(guess: &str, answer: &str) -> bool {
+    let guess = fold(guess);
+    let answer = fold(answer);
+    if guess.is_empty() || answer.is_empty() {
+        return false;
+    }
+    if guess == answer || format!(" {guess} ").contains(&format!(" {answer} ")) {
+        return true;
+    }
+    let longest = guess.chars().count().max(answer.chars().count());
+    let similarity = 1.0 - edit_distance(&guess, &answer) as f32 / longest as f32;
+    similarity >= MATCH_THRESHOLD
+}
+
+/// Lowercase words without punctuation or a leading "the".
+fn fold(text: &str) -> String {
+    let words = text
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>();
+    let words = words.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["the", rest @ ..] if !rest.is_empty() => rest.join(" "),
+        _ => words.join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_loose_guesses() {
+        assert!(matches("bohemian rapsody", "Bohemian Rhapsody"));
+        assert!(matches("Beatles", "The Beatles"));
+        assert!(matches("the beatles", "Beatles"));
+        assert!(matches("i think it's africa by toto", "Africa"));
+        assert!(matches("DON'T STOP ME NOW!!", "Don't Stop Me Now"));
+
+        assert!(!matches("africa", "America"));
+        assert!(!matches("afric", "Africa by the lake"));
+        assert!(!matches("frica", "Africa Unite"));
+        assert!(!matches("", "Africa"));
+        assert!(!matches("!!!", "Africa"));
+    }
+
+    #[test]
+    fn ranks_scores_high_to_low() {
+        let mut scoreboard = Scoreboard::default();
+        assert!(scoreboard.ranked().is_empty());
+        scoreboard.add(UserId::new(3), 2);
+        scoreboard.add(UserId::new(1), 1);
+        scoreboard.add(UserId::new(2), 2);
+        scoreboard.add(UserId::new(1), 3);
+        assert_eq!(
+            scoreboard.ranked(),
+            [
+                (UserId::new(1), 4),
+                (UserId::new(2), 2),
+                (UserId::new(3), 2)
+            ]
+        );
+    }
+}
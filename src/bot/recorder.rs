@@ -0,0 +1,337 @@
+use super::bot::Error;
+
+use audiopus::coder::Decoder as OpusDecoder;
+use audiopus::{Channels, SampleRate};
+use serenity::all::{ChannelId, GuildId, UserId};
+use serenity::async_trait;
+use songbird::events::context_data::VoiceTick;
+use songbird::packet::rtp::RtpExtensionPacket;
+use songbird::packet::{Packet, PacketSize};
+use songbird::{Event, EventContext, EventHandler as VoiceEventHandler};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const RECORDINGS_DIR: &str = "data/recordings";
+pub const MAX_RECORDING: Duration = Duration::from_secs(10 * 60);
+const SAMPLE_RATE: u32 = 48_000;
+/// One second of a recorded file, 16-bit mono.
+pub const BYTES_PER_SECOND: u64 = SAMPLE_RATE as u64 * 2;
+/// Frames per voice tick, Discord sends 20ms of audio at a time.
+const TICK_FRAMES: usize = 960;
+/// Room for the longest Opus frame, 120ms of stereo.
+const MAX_DECODED: usize = 5760 * 2;
+
+/// One finished file of a recording.
+pub struct RecordedFile {
+    pub path: PathBuf,
+    /// The speaker, `None` for the mix and for speakers we never matched to a user.
+    pub user: Option<UserId>,
+    pub size: u64,
+}
+
+pub struct FinishedRecording {
+    pub channel_id: ChannelId,
+    pub length: Duration,
+    pub files: Vec<RecordedFile>,
+    pub dir: PathBuf,
+}
+
+/// Records the voice channel into one mono WAV per speaker plus a mix of
+/// everyone, streamed to `data/recordings/` as the ticks come in.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    active: Arc<Mutex<Option<Recording>>>,
+    // Tells handlers of older recordings apart from the current one
+    generation: Arc<AtomicU64>,
+}
+
+struct Recording {
+    generation: u64,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    started_at: Instant,
+    dir: PathBuf,
+    ticks: u64,
+    speakers: HashMap<u32, Speaker>,
+    users: HashMap<u32, UserId>,
+    mix: WavWriter,
+}
+
+struct Speaker {
+    decoder: OpusDecoder,
+    writer: WavWriter,
+    // Decoded mono samples not written yet, packets can hold more than a tick
+    pending: Vec<i16>,
+}
+
+impl Recorder {
+    pub fn is_recording(&self, guild_id: GuildId) -> bool {
+        self.active
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|recording| recording.guild_id == guild_id)
+    }
+
+    /// Starts a recording and returns the handler to register on the call.
+    pub fn start(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<RecordingHandler, Error> {
+        let mut active = self.active.lock().unwrap();
+        if active.is_some() {
+            return Err("Already recording, use !record stop first".into());
+        }
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let dir = PathBuf::from(RECORDINGS_DIR).join(format!("{guild_id}-{started}"));
+        fs::create_dir_all(&dir)?;
+
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        *active = Some(Recording {
+            generation,
+            guild_id,
+            channel_id,
+            started_at: Instant::now(),
+            mix: WavWriter::create(dir.join("mix.wav"))?,
+            dir,
+            ticks: 0,
+            speakers: HashMap::new(),
+            users: HashMap::new(),
+        });
+        Ok(RecordingHandler {
+            recorder: self.clone(),
+            generation,
+        })
+    }
+
+    /// Stops the recording in `guild_id` and finalizes its files.
+    pub fn stop(&self, guild_id: GuildId) -> Result<FinishedRecording, Error> {
+        let recording = self
+            .active
+            .lock()
+            .unwrap()
+            .take_if(|recording| recording.guild_id == guild_id)
+            .ok_or("Nothing is being recorded")?;
+        self.finish(recording)
+    }
+
+    /// Stops the recording once it hits `MAX_RECORDING`, if it is still the
+    /// one started as `generation`.
+    pub fn stop_if_expired(&self, generation: u64) -> Option<Result<FinishedRecording, Error>> {
+        let recording = self
+            .active
+            .lock()
+            .unwrap()
+            .take_if(|recording| recording.generation == generation)?;
+        Some(self.finish(recording))
+    }
+
+    fn finish(&self, recording: Recording) -> Result<FinishedRecording, Error> {
+        let length = recording.started_at.elapsed();
+        let mut files = Vec::new();
+        for (ssrc, speaker) in recording.speakers {
+            let user = recording.users.get(&ssrc).copied();
+            let path = speaker.writer.finish()?;
+            // Name files after the speaker where we know who it is
+            let named = match user {
+                Some(user) => recording.dir.join(format!("{user}.wav")),
+                None => recording.dir.join(format!("ssrc-{ssrc}.wav")),
+            };
+            fs::rename(&path, &named)?;
+            files.push(RecordedFile {
+                size: fs::metadata(&named)?.len(),
+                path: named,
+                user,
+            });
+        }
+        let mix = recording.mix.finish()?;
+        files.push(RecordedFile {
+            size: fs::metadata(&mix)?.len(),
+            path: mix,
+            user: None,
+        });
+
+        Ok(FinishedRecording {
+            channel_id: recording.channel_id,
+            length,
+            files,
+            dir: recording.dir,
+        })
+    }
+}
+
+/// Feeds voice events into the recording it was started for, and removes
+/// itself from the call once that recording is over.
+#[derive(Clone)]
+pub struct RecordingHandler {
+    recorder: Recorder,
+    pub generation: u64,
+}
+
+#[async_trait]
+impl VoiceEventHandler for RecordingHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let mut active = self.recorder.active.lock().unwrap();
+        let recording = match active.as_mut() {
+            Some(recording) if recording.generation == self.generation => recording,
+            _ => return Some(Event::Cancel),
+        };
+
+        match ctx {
+            EventContext::SpeakingStateUpdate(speaking) => {
+                if let Some(user) = speaking.user_id {
+                    recording.users.insert(speaking.ssrc, UserId::new(user.0));
+                }
+            }
+            // Past the limit the recording only waits to be collected
+            EventContext::VoiceTick(tick) if recording.started_at.elapsed() < MAX_RECORDING => {
+                if let Err(e) = recording.tick(tick) {
+                    warn!("Could not write recording: {e}");
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Recording {
+    /// Writes one tick to every speaker's file and to the mix.
+    fn tick(&mut self, tick: &VoiceTick) -> Result<(), Error> {
+        for (ssrc, data) in &tick.speaking {
+            if !self.speakers.contains_key(ssrc) {
+                let path = self.dir.join(format!("{ssrc}.part.wav"));
+                let mut writer = WavWriter::create(path)?;
+                // Speakers joining late start with silence, to line up with the mix
+                writer.write_silence(self.ticks as usize * TICK_FRAMES)?;
+                self.speakers.insert(
+                    *ssrc,
+                    Speaker {
+                        decoder: OpusDecoder::new(SampleRate::Hz48000, Channels::Stereo)?,
+                        writer,
+                        pending: Vec::new(),
+                    },
+                );
+            }
+            let speaker = self.speakers.get_mut(ssrc).unwrap();
+            let opus = data.packet.as_ref().and_then(|packet| {
+                let rtp = packet.rtp();
+                let payload = rtp.payload();
+                let body = payload.get(packet.payload_offset..packet.payload_end_pad)?;
+                // Header extensions sit in front of the Opus data
+                let start = match rtp.get_extension() {
+                    0 => 0,
+                    _ => RtpExtensionPacket::new(body)?.packet_size(),
+                };
+                body.get(start..).map(<[u8]>::to_vec)
+            });
+            speaker.decode(opus.as_deref())?;
+        }
+
+        let mut mix = vec![0i32; TICK_FRAMES];
+        for speaker in self.speakers.values_mut() {
+            let frames = speaker.take_tick();
+            for (mixed, sample) in mix.iter_mut().zip(&frames) {
+                *mixed += *sample as i32;
+            }
+            speaker.writer.write(&frames)?;
+        }
+        let mix = mix
+            .into_iter()
+            .map(|sample| sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+            .collect::<Vec<_>>();
+        self.mix.write(&mix)?;
+        self.ticks += 1;
+        Ok(())
+    }
+}
+
+impl Speaker {
+    /// Decodes a packet into `pending`, concealing the loss when it is missing.
+    fn decode(&mut self, opus: Option<&[u8]>) -> Result<(), Error> {
+        let mut stereo = vec![0i16; MAX_DECODED];
+        let frames = self.decoder.decode(opus, &mut stereo[..], false)?;
+        self.pending.extend(
+            stereo[..frames * 2]
+                .chunks_exact(2)
+                .map(|pair| ((pair[0] as i32 + pair[1] as i32) / 2) as i16),
+        );
+        Ok(())
+    }
+
+    /// Exactly one tick of audio, padded with silence when the speaker is quiet.
+    fn take_tick(&mut self) -> Vec<i16> {
+        let take = self.pending.len().min(TICK_FRAMES);
+        let mut frames = self.pending.drain(..take).collect::<Vec<_>>();
+        frames.resize(TICK_FRAMES, 0);
+        frames
+    }
+}
+
+/// Mono 16-bit WAV written as it goes, the sizes in the header are filled
+/// in by `finish`.
+struct WavWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: PathBuf) -> Result<Self, Error> {
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM, one channel
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        file.write_all(&2u16.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data\0\0\0\0")?;
+        Ok(Self {
+            path,
+            file,
+            data_len: 0,
+        })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<(), Error> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    fn write_silence(&mut self, frames: usize) -> Result<(), Error> {
+        let silence = [0i16; TICK_FRAMES];
+        let mut left = frames;
+        while left > 0 {
+            let count = left.min(TICK_FRAMES);
+            self.write(&silence[..count])?;
+            left -= count;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<PathBuf, Error> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + self.data_len).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(self.path)
+    }
+}
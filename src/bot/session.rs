@@ -0,0 +1,61 @@
+use super::bot::Error;
+use super::status::LoopMode;
+use super::track::QueuedTrack;
+use crate::storage::JsonStore;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::GuildId;
+use std::collections::HashMap;
+
+const SESSIONS_PATH: &str = "data/sessions.json";
+
+/// Everything needed to pick playback back up after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub channel_id: u64,
+    pub current: Option<QueuedTrack>,
+    pub position_secs: u64,
+    pub queue: Vec<QueuedTrack>,
+    pub loop_mode: LoopMode,
+    pub volume: f32,
+    pub saved_at: u64,
+}
+
+#[derive(Clone)]
+pub struct SessionStore {
+    store: JsonStore<HashMap<u64, Session>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self {
+            store: JsonStore::new(SESSIONS_PATH),
+        }
+    }
+
+    pub fn save(&self, guild_id: GuildId, session: Session) -> Result<(), Error> {
+        self.store.update(|sessions| {
+            sessions.insert(guild_id.get(), session);
+        })
+    }
+
+    pub fn load(&self, guild_id: GuildId) -> Result<Option<Session>, Error> {
+        Ok(self.store.load()?.remove(&guild_id.get()))
+    }
+
+    /// The most recently saved session across all guilds.
+    pub fn latest(&self) -> Result<Option<(GuildId, Session)>, Error> {
+        Ok(self
+            .store
+            .load()?
+            .into_iter()
+            .max_by_key(|(_, session)| session.saved_at)
+            .map(|(guild_id, session)| (GuildId::new(guild_id), session)))
+    }
+
+    pub fn remove(&self, guild_id: GuildId) -> Result<(), Error> {
+        self.store.update(|sessions| {
+            sessions.remove(&guild_id.get());
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use super::bot::Error;
+use super::effects::Effect;
+use super::permissions::Access;
+use crate::storage::JsonStore;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{GuildId, RoleId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const SETTINGS_PATH: &str = "data/settings.json";
+
+/// Per-guild configuration. Missing fields fall back to their defaults so
+/// older settings files keep loading as new options are added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Share of the listeners in the voice channel that must vote to skip.
+    pub vote_skip_percent: u8,
+    /// Role that counts as DJ, falls back to a role named "DJ" when unset.
+    pub dj_role: Option<RoleId>,
+    /// Per-command overrides of the default access policy.
+    pub permissions: HashMap<String, Access>,
+    /// How many tracks one user may have waiting in the queue.
+    pub max_tracks_per_user: Option<usize>,
+    /// Longest track, in seconds, that can be queued.
+    pub max_track_length_secs: Option<u64>,
+    /// Play the queue round-robin across requesters instead of FIFO.
+    pub fair_queue: bool,
+    /// Keep playing related tracks once the queue runs out.
+    pub autoplay: bool,
+    /// Level tracks toward `target_lufs` instead of playing them as uploaded.
+    pub normalize: bool,
+    pub target_lufs: f32,
+    /// Audio effect applied to everything played, see `!fx`.
+    pub effect: Option<Effect>,
+    /// Seconds the end of a track overlaps the start of the next one.
+    pub crossfade_secs: u64,
+    /// Say the title of each track as it starts.
+    pub announce: bool,
+    /// Set by a bot owner, `!record` refuses to start without it.
+    pub recording_allowed: bool,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            vote_skip_percent: 50,
+            dj_role: None,
+            permissions: HashMap::new(),
+            max_tracks_per_user: None,
+            max_track_length_secs: None,
+            fair_queue: false,
+            autoplay: false,
+            normalize: false,
+            target_lufs: -14.0,
+            effect: None,
+            crossfade_secs: 0,
+            announce: false,
+            recording_allowed: false,
+        }
+    }
+}
+
+/// Guild settings kept in `data/settings.json`, parsed once and written
+/// through on every update.
+#[derive(Clone)]
+pub struct Settings {
+    store: JsonStore<HashMap<u64, GuildSettings>>,
+    loaded: Arc<Mutex<Option<HashMap<u64, GuildSettings>>>>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            store: JsonStore::new(SETTINGS_PATH),
+            loaded: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn get(&self, guild_id: GuildId) -> GuildSettings {
+        let mut loaded = self.loaded.lock().unwrap();
+        if loaded.is_none() {
+            match self.store.load() {
+                Ok(settings) => *loaded = Some(settings),
+                Err(e) => {
+                    warn!("Could not load guild settings, using defaults: {e}");
+                    return GuildSettings::default();
+                }
+            }
+        }
+        loaded
+            .as_ref()
+            .and_then(|settings| settings.get(&guild_id.get()).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn update(
+        &self,
+        guild_id: GuildId,
+        f: impl FnOnce(&mut GuildSettings),
+    ) -> Result<GuildSettings, Error> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let (updated, settings) = self.store.update(|settings| {
+            let entry = settings.entry(guild_id.get()).or_default();
+            f(entry);
+            (entry.clone(), settings.clone())
+        })?;
+        *loaded = Some(settings);
+        Ok(updated)
+    }
+}
@@ -0,0 +1,209 @@
+use super::bot::Error;
+use crate::storage::JsonStore;
+
+use serenity::all::{ButtonStyle, CreateActionRow, CreateButton, GuildId, UserId};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const SOUNDS_DIR: &str = "data/sounds";
+const CLIPS_PATH: &str = "data/sounds/clips.json";
+pub const EXTENSIONS: [&str; 5] = ["mp3", "ogg", "opus", "wav", "flac"];
+/// Discord allows 25 buttons on a message, the panel shows every clip.
+pub const MAX_CLIPS: usize = 25;
+pub const MAX_CLIP_BYTES: usize = 2 * 1024 * 1024;
+const COOLDOWN: Duration = Duration::from_secs(5);
+/// Prefix of the custom id of panel buttons, followed by the clip name.
+pub const BUTTON_PREFIX: &str = "sb:";
+
+/// Short clips per guild, stored as files under `data/sounds/<guild>/`
+/// with their volumes kept next to them.
+#[derive(Clone)]
+pub struct Soundboard {
+    dir: PathBuf,
+    // Guild -> clip -> volume, clips without an entry play at full volume
+    volumes: JsonStore<HashMap<u64, HashMap<String, f32>>>,
+    last_played: Arc<Mutex<HashMap<UserId, Instant>>>,
+}
+
+impl Soundboard {
+    pub fn new() -> Self {
+        Self {
+            dir: PathBuf::from(SOUNDS_DIR),
+            volumes: JsonStore::new(CLIPS_PATH),
+            last_played: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn guild_dir(&self, guild_id: GuildId) -> PathBuf {
+        self.dir.join(guild_id.get().to_string())
+    }
+
+    /// Clip names of the guild, sorted.
+    pub fn list(&self, guild_id: GuildId) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(self.guild_dir(guild_id)) else {
+            return Vec::new();
+        };
+        let mut names = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let extension = path.extension()?.to_str()?;
+                if !EXTENSIONS.contains(&extension) {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// File of the clip, `None` for names no clip could have.
+    pub fn path(&self, guild_id: GuildId, name: &str) -> Option<PathBuf> {
+        validate_name(name).ok()?;
+        let dir = self.guild_dir(guild_id);
+        EXTENSIONS
+            .iter()
+            .map(|extension| dir.join(format!("{name}.{extension}")))
+            .find(|path| path.exists())
+    }
+
+    /// Stores an uploaded clip, replacing one with the same name.
+    pub fn save(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        extension: &str,
+        content: &[u8],
+    ) -> Result<(), Error> {
+        validate_name(name)?;
+        if !EXTENSIONS.contains(&extension) {
+            return Err(format!("Clips must be one of {}", EXTENSIONS.join(", ")).into());
+        }
+        if content.len() > MAX_CLIP_BYTES {
+            return Err(format!("Clips can be at most {} KiB", MAX_CLIP_BYTES / 1024).into());
+        }
+        let replacing = self.path(guild_id, name);
+        if replacing.is_none() && self.list(guild_id).len() >= MAX_CLIPS {
+            return Err(format!("The soundboard is full, it holds {MAX_CLIPS} clips").into());
+        }
+
+        let dir = self.guild_dir(guild_id);
+        fs::create_dir_all(&dir)?;
+        if let Some(old) = replacing {
+            fs::remove_file(old)?;
+        }
+        fs::write(dir.join(format!("{name}.{extension}")), content)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, guild_id: GuildId, name: &str) -> Result<(), Error> {
+        validate_name(name)?;
+        let path = self
+            .path(guild_id, name)
+            .ok_or(format!("There is no clip named {name}"))?;
+        fs::remove_file(path)?;
+        self.volumes.update(|volumes| {
+            if let Some(clips) = volumes.get_mut(&guild_id.get()) {
+                clips.remove(name);
+            }
+        })?;
+        Ok(())
+    }
+
+    pub fn volume(&self, guild_id: GuildId, name: &str) -> f32 {
+        self.volumes
+            .load()
+            .ok()
+            .and_then(|volumes| volumes.get(&guild_id.get())?.get(name).copied())
+            .unwrap_or(1.0)
+    }
+
+    pub fn set_volume(&self, guild_id: GuildId, name: &str, volume: f32) -> Result<(), Error> {
+        validate_name(name)?;
+        if self.path(guild_id, name).is_none() {
+            return Err(format!("There is no clip named {name}").into());
+        }
+        self.volumes.update(|volumes| {
+            volumes
+                .entry(guild_id.get())
+                .or_default()
+                .insert(name.to_string(), volume);
+        })
+    }
+
+    /// Says how long is left of `user`'s cooldown, if they are in one.
+    pub fn check_cooldown(&self, user: UserId) -> Result<(), Error> {
+        if let Some(at) = self.last_played.lock().unwrap().get(&user) {
+            let remaining = COOLDOWN.saturating_sub(at.elapsed());
+            if !remaining.is_zero() {
+                return Err(format!(
+                    "Slow down, you can play another clip in {:.1}s",
+                    remaining.as_secs_f32()
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn start_cooldown(&self, user: UserId) {
+        self.last_played
+            .lock()
+            .unwrap()
+            .insert(user, Instant::now());
+    }
+
+    /// Button rows playing each clip when clicked.
+    pub fn panel(&self, guild_id: GuildId) -> Vec<CreateActionRow> {
+        self.list(guild_id)
+            .chunks(5)
+            .map(|names| {
+                CreateActionRow::Buttons(
+                    names
+                        .iter()
+                        .map(|name| {
+                            CreateButton::new(format!("{BUTTON_PREFIX}{name}"))
+                                .label(name)
+                                .style(ButtonStyle::Secondary)
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Clip names double as file names and button ids, so path separators and
+/// dots never make it into one.
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err("Clip names are up to 32 lowercase letters, digits, - or _".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_names_leaving_the_guild_dir() {
+        for name in ["../clips", "a/b", "a\\b", "..", "", "Loud"] {
+            assert!(validate_name(name).is_err(), "{name}");
+        }
+        assert!(validate_name("air-horn_2").is_ok());
+
+        let soundboard = Soundboard::new();
+        assert_eq!(soundboard.path(GuildId::new(1), "../clips"), None);
+        assert!(soundboard.set_volume(GuildId::new(1), "../x", 0.5).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use super::bot::Error;
+
+use serenity::async_trait;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Longest text spoken in one go, espeak happily reads out a novel.
+pub const MAX_SPEECH_CHARS: usize = 300;
+
+/// Turns text into speech, as a WAV file held in memory.
+#[async_trait]
+pub trait Synthesizer: Send + Sync {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, Error>;
+}
+
+/// The backend picked by `TTS_ENGINE`: `piper` with `PIPER_MODEL` pointing at
+/// a voice model, or espeak-ng otherwise, voiced by `TTS_VOICE`.
+pub fn from_env() -> Arc<dyn Synthesizer> {
+    match (env::var("TTS_ENGINE").as_deref(), env::var("PIPER_MODEL")) {
+        (Ok("piper"), Ok(model)) => Arc::new(Piper {
+            model: PathBuf::from(model),
+        }),
+        _ => Arc::new(Espeak {
+            voice: env::var("TTS_VOICE").unwrap_or_else(|_| "en".to_string()),
+        }),
+    }
+}
+
+pub struct Espeak {
+    voice: String,
+}
+
+#[async_trait]
+impl Synthesizer for Espeak {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let output = Command::new("espeak-ng")
+            .args(["--stdout", "-v", &self.voice, "--"])
+            .arg(text)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+pub struct Piper {
+    model: PathBuf,
+}
+
+impl Piper {
+    /// Piper writes bare samples, the rate lives in the model's config.
+    fn sample_rate(&self) -> u32 {
+        let config = self.model.with_extension("onnx.json");
+        fs::read_to_string(config)
+            .ok()
+            .and_then(|config| serde_json::from_str::<serde_json::Value>(&config).ok())
+            .and_then(|config| config["audio"]["sample_rate"].as_u64())
+            .map_or(22_050, |rate| rate as u32)
+    }
+}
+
+#[async_trait]
+impl Synthesizer for Piper {
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let mut child = Command::new("piper")
+            .arg("--model")
+            .arg(&self.model)
+            .args(["--output-raw", "--quiet"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Piper reads one utterance per line
+        let mut stdin = child.stdin.take().ok_or("piper has no stdin")?;
+        stdin.write_all(text.replace('\n', " ").as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+        }
+        let samples = output
+            .stdout
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        Ok(wav(&samples, self.sample_rate()))
+    }
+}
+
+/// Mono 16-bit PCM samples wrapped in a WAV header.
+pub fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+/// A synthesizer for tests that need speech without espeak installed.
+#[cfg(test)]
+pub mod stub {
+    use super::*;
+    use std::f32::consts::PI;
+
+    pub const RATE: u32 = 16_000;
+
+    /// Hums a 440 Hz tone for 50ms per character instead of speaking.
+    pub struct SineStub;
+
+    #[async_trait]
+    impl Synthesizer for SineStub {
+        async fn synthesize(&self, text: &str) -> Result<Vec<u8>, Error> {
+            let frames = text.chars().count() * RATE as usize / 20;
+            let samples = (0..frames)
+                .map(|n| {
+                    let t = n as f32 / RATE as f32;
+                    ((2.0 * PI * 440.0 * t).sin() * i16::MAX as f32 * 0.5) as i16
+                })
+                .collect::<Vec<_>>();
+            Ok(wav(&samples, RATE))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stub::{SineStub, RATE};
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    /// Decodes WAV bytes the way songbird would, returning rate and frames.
+    fn decode(bytes: Vec<u8>) -> (u32, u64) {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                Hint::new().with_extension("wav"),
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format;
+        let track = format.default_track().unwrap().clone();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .unwrap();
+
+        let mut frames = 0;
+        while let Ok(packet) = format.next_packet() {
+            frames += decoder.decode(&packet).unwrap().frames() as u64;
+        }
+        (track.codec_params.sample_rate.unwrap(), frames)
+    }
+
+    #[tokio::test]
+    async fn stub_speech_decodes() {
+        let synthesizer: Arc<dyn Synthesizer> = Arc::new(SineStub);
+        let bytes = synthesizer.synthesize("now playing").await.unwrap();
+
+        let (rate, frames) = decode(bytes);
+        assert_eq!(rate, RATE);
+        assert_eq!(frames, 11 * RATE as u64 / 20);
+    }
+
+    #[test]
+    fn empty_wav_is_valid() {
+        assert_eq!(decode(wav(&[], RATE)), (RATE, 0));
+    }
+}
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
 #[derive(Debug, PartialEq)]
 pub enum Status {
     Playing,
@@ -8,6 +11,27 @@ pub enum Status {
 
 impl Status {
     pub fn should_enqueue(current_status: Status) -> bool {
-        return current_status != Status::Disconnected || current_status != Status::Idle;
+        current_status != Status::Disconnected || current_status != Status::Idle
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    Track,
+    Queue,
+}
+
+impl FromStr for LoopMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(LoopMode::Off),
+            "track" | "song" | "one" => Ok(LoopMode::Track),
+            "queue" | "all" => Ok(LoopMode::Queue),
+            _ => Err(format!("Unknown loop mode `{s}`, use off, track or queue")),
+        }
     }
 }
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+use songbird::input::AuxMetadata;
+use songbird::tracks::TrackHandle;
+use std::time::{Duration, SystemTime};
+
+/// A track waiting in the queue. Only the descriptor is kept here, the
+/// audio stream is created by the driver right before the track plays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedTrack {
+    pub url: String,
+    #[serde(default)]
+    pub requester: Option<UserId>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub duration: Option<Duration>,
+    /// Picked by autoplay rather than requested by someone.
+    #[serde(default)]
+    pub autoplay: bool,
+}
+
+impl QueuedTrack {
+    pub fn new(url: String, requester: UserId) -> Self {
+        Self {
+            url,
+            requester: Some(requester),
+            title: None,
+            artist: None,
+            duration: None,
+            autoplay: false,
+        }
+    }
+
+    pub fn autoplay(url: String) -> Self {
+        Self {
+            url,
+            requester: None,
+            title: None,
+            artist: None,
+            duration: None,
+            autoplay: true,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: &AuxMetadata) -> Self {
+        self.title = metadata.title.clone().or_else(|| metadata.track.clone());
+        self.artist = metadata.artist.clone().or_else(|| metadata.channel.clone());
+        self.duration = metadata.duration;
+        self
+    }
+
+    /// Title for queue listings, the URL when metadata never resolved.
+    pub fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// What a track is called, for quiz players to guess and lyrics to be
+/// looked up by.
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub title: String,
+    pub artist: Option<String>,
+}
+
+impl Answer {
+    /// Splits "Artist - Title (Official Video)" uploads, falling back to the
+    /// uploader as the artist.
+    pub fn from_track(track: &QueuedTrack) -> Option<Self> {
+        let title = strip_brackets(track.title.as_deref()?);
+        let (artist, title) = match title.split_once(" - ") {
+            Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+            None => (
+                track.artist.as_deref().map(|artist| {
+                    artist
+                        .trim_end_matches(" - Topic")
+                        .trim_end_matches("VEVO")
+                        .trim()
+                        .to_string()
+                }),
+                title.trim().to_string(),
+            ),
+        };
+        if !has_words(&title) {
+            return None;
+        }
+        Some(Self {
+            title,
+            artist: artist.filter(|artist| has_words(artist)),
+        })
+    }
+}
+
+impl std::fmt::Display for Answer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.artist {
+            Some(artist) => write!(f, "**{}** by **{artist}**", self.title),
+            None => write!(f, "**{}**", self.title),
+        }
+    }
+}
+
+pub struct CurrentTrack {
+    pub track: QueuedTrack,
+    pub handle: TrackHandle,
+    pub started_at: SystemTime,
+}
+
+/// Drops "(Official Video)", "[HD]" and the like.
+fn strip_brackets(title: &str) -> String {
+    let mut depth = 0usize;
+    let mut stripped = String::with_capacity(title.len());
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn has_words(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+/// Identity of a track across the different URL shapes of the same video.
+pub fn track_key(url: &str) -> String {
+    youtube_id(url).unwrap_or_else(|| url.trim_end_matches('/').to_string())
+}
+
+pub fn youtube_id(url: &str) -> Option<String> {
+    let parsed = url::Url::parse(url).ok()?;
+    match parsed.host_str()? {
+        "youtu.be" => parsed.path_segments()?.next().map(|id| id.to_string()),
+        host if host.ends_with("youtube.com") => parsed
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uploaded(title: &str, uploader: Option<&str>) -> Option<Answer> {
+        let mut track = QueuedTrack::autoplay("https://youtu.be/x".to_string());
+        track.title = Some(title.to_string());
+        track.artist = uploader.map(str::to_string);
+        Answer::from_track(&track)
+    }
+
+    #[test]
+    fn strips_brackets() {
+        assert_eq!(strip_brackets("Africa (Official Video) [HD]"), "Africa");
+        assert_eq!(strip_brackets("Song (feat. A (B) C)  Remix"), "Song Remix");
+        assert_eq!(strip_brackets("Unclosed (live"), "Unclosed");
+        assert_eq!(strip_brackets("Stray ) bracket"), "Stray bracket");
+    }
+
+    #[test]
+    fn splits_artist_from_title() {
+        let answer = uploaded("Toto - Africa (Official HD Video)", Some("TotoVEVO")).unwrap();
+        assert_eq!(answer.title, "Africa");
+        assert_eq!(answer.artist.as_deref(), Some("Toto"));
+
+        let answer = uploaded("Africa", Some("Toto - Topic")).unwrap();
+        assert_eq!(answer.artist.as_deref(), Some("Toto"));
+
+        let answer = uploaded("Africa", Some("VEVO")).unwrap();
+        assert_eq!(answer.artist, None);
+        assert!(uploaded("(Official Video)", Some("Toto")).is_none());
+    }
+}
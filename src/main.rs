@@ -4,9 +4,11 @@ use serenity::{
 };
 use songbird::SerenityInit;
 use std::{collections::HashSet, env};
-use tracing::info;
+use tracing::{info, warn};
 
+mod storage;
 mod tarkov;
+mod text;
 
 mod bot;
 use bot::bot::{Bot, Error as BotError};
@@ -32,6 +34,9 @@ async fn main() {
             skip_checks_for_owners: true,
             manual_cooldowns: false,
             owners: HashSet::from([UserId::new(90550255229091840)]),
+            event_handler: |ctx, event, framework, data| {
+                Box::pin(bot::events::handle(ctx, event, framework, data))
+            },
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("!".into()),
                 edit_tracker: None,
@@ -47,7 +52,19 @@ async fn main() {
                 // let activity = ActivityData::custom("mimis");
                 // ctx.set_presence(Some(activity), Idle);
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Bot::new())
+                let bot = Bot::new();
+
+                // Pick the last session back up after a redeploy
+                if env::var("RESUME_SESSION").is_ok() {
+                    let manager = songbird::get(ctx)
+                        .await
+                        .expect("Songbird voice client err")
+                        .clone();
+                    if let Err(e) = bot.driver.resume_latest_session(manager).await {
+                        warn!("Could not resume last session: {e}");
+                    }
+                }
+                Ok(bot)
             })
         })
         .build();
@@ -0,0 +1,73 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A value persisted as a single JSON file under `data/`.
+///
+/// Reads fall back to `T::default()` when the file does not exist yet, and
+/// writes go through a temporary file so a crash never leaves half a document.
+pub struct JsonStore<T> {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for JsonStore<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            lock: Arc::clone(&self.lock),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> JsonStore<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Arc::new(Mutex::new(())),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn load(&self) -> Result<T, Error> {
+        let _guard = self.lock.lock().unwrap();
+        self.read()
+    }
+
+    /// Read-modify-write under the store lock.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, Error> {
+        let _guard = self.lock.lock().unwrap();
+        let mut value = self.read()?;
+        let result = f(&mut value);
+        self.write(&value)?;
+        Ok(result)
+    }
+
+    fn read(&self) -> Result<T, Error> {
+        if !self.path.exists() {
+            return Ok(T::default());
+        }
+        let content = fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn write(&self, value: &T) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(value)?)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
@@ -1,2 +1,9 @@
+pub mod ammo;
+pub mod chart;
+pub mod client;
+pub mod data;
+pub mod hideout;
+pub mod progress;
+pub mod search;
 pub mod types;
 pub mod utils;
@@ -0,0 +1,200 @@
+use crate::tarkov::types::Ammo;
+
+/// Armor classes as the game numbers them.
+pub const ARMOR_CLASSES: std::ops::RangeInclusive<u64> = 1..=6;
+/// Longest round name kept in the table.
+const NAME_WIDTH: usize = 18;
+
+/// How well a round deals with an armor class. Class `n` armor holds back
+/// penetration up to about `10n`, so this compares against that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    /// Goes through from the first hit.
+    Reliable,
+    /// Goes through after a few hits.
+    Often,
+    /// Only goes through once the armor is worn down.
+    Worn,
+    /// Hardly ever goes through, even worn armor stops it.
+    Barely,
+}
+
+impl Rating {
+    pub fn of(penetration: u64, class: u64) -> Self {
+        let armor = class * 10;
+        match penetration {
+            pen if pen >= armor + 10 => Rating::Reliable,
+            pen if pen >= armor => Rating::Often,
+            pen if pen + 10 >= armor => Rating::Worn,
+            _ => Rating::Barely,
+        }
+    }
+
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Rating::Reliable => "🟩",
+            Rating::Often => "🟨",
+            Rating::Worn => "🟧",
+            Rating::Barely => "🟥",
+        }
+    }
+}
+
+pub const LEGEND: &str =
+    "🟩 penetrates from the first hit 🟨 after a few hits 🟧 once the armor is worn 🟥 barely";
+
+/// Rounds of the caliber `query` names, by highest penetration. "5.56",
+/// "556x45" and "Caliber556x45NATO" all name 5.56x45mm, a query naming
+/// several calibers lists them instead.
+pub fn rounds<'a>(ammo: &'a [Ammo], query: &str) -> Result<Vec<&'a Ammo>, String> {
+    let query = key(query.trim().trim_start_matches("Caliber"));
+    if query.is_empty() {
+        return Err("Which caliber? Try !ammo 5.56 or !ammo 7.62x39".to_string());
+    }
+
+    let mut calibers = ammo
+        .iter()
+        .map(|round| round.caliber.as_str())
+        .collect::<Vec<_>>();
+    calibers.sort_unstable();
+    calibers.dedup();
+    let caliber_key = |caliber: &str| key(caliber.trim_start_matches("Caliber"));
+
+    let exact = calibers
+        .iter()
+        .filter(|caliber| caliber_key(caliber) == query)
+        .collect::<Vec<_>>();
+    let found = if exact.is_empty() {
+        calibers
+            .iter()
+            .filter(|caliber| caliber_key(caliber).starts_with(&query))
+            .collect::<Vec<_>>()
+    } else {
+        exact
+    };
+
+    match found.as_slice() {
+        [] => Err("No caliber matches that, try something like 5.45x39".to_string()),
+        [caliber] => {
+            let mut rounds = ammo
+                .iter()
+                .filter(|round| round.caliber == **caliber)
+                .collect::<Vec<_>>();
+            rounds.sort_by(|a, b| {
+                b.penetration_power
+                    .cmp(&a.penetration_power)
+                    .then(b.damage.cmp(&a.damage))
+            });
+            Ok(rounds)
+        }
+        several => Err(format!(
+            "That could be {}, which one?",
+            several
+                .iter()
+                .map(|caliber| caliber.trim_start_matches("Caliber"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Rounds numbered as the chart labels them, with their stats and a rating
+/// against each armor class.
+pub fn table(rounds: &[&Ammo]) -> String {
+    let classes = ARMOR_CLASSES
+        .map(|class| format!("{class:<2}"))
+        .collect::<String>();
+    let mut lines = vec![format!(
+        "{:>2} {:<NAME_WIDTH$} {:>3} {:>3} {:>4} {:>4}  {}",
+        "#", "Round", "Dmg", "Pen", "ArmD", "Rec", classes
+    )];
+    for (i, round) in rounds.iter().enumerate() {
+        let name = round
+            .item
+            .short_name
+            .chars()
+            .take(NAME_WIDTH)
+            .collect::<String>();
+        let ratings = ARMOR_CLASSES
+            .map(|class| Rating::of(round.penetration_power, class).emoji())
+            .collect::<String>();
+        lines.push(format!(
+            "{:>2} {name:<NAME_WIDTH$} {:>3} {:>3} {:>4} {:>+4.0}% {ratings}",
+            i + 1,
+            round.damage,
+            round.penetration_power,
+            round.armor_damage,
+            round.recoil * 100.0
+        ));
+    }
+    format!("```\n{}\n```", lines.join("\n"))
+}
+
+/// Lowercase letters and digits only, so "7.62x39" and "762x39" compare equal.
+fn key(text: &str) -> String {
+    text.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarkov::types::ItemName;
+
+    fn round(name: &str, caliber: &str, penetration: u64, damage: u64) -> Ammo {
+        Ammo {
+            item: ItemName {
+                id: name.to_string(),
+                name: name.to_string(),
+                short_name: name.to_string(),
+            },
+            caliber: caliber.to_string(),
+            damage,
+            armor_damage: 50,
+            penetration_power: penetration,
+            recoil: 0.0,
+        }
+    }
+
+    fn ammo() -> Vec<Ammo> {
+        vec![
+            round("M855", "Caliber556x45NATO", 31, 54),
+            round("M995", "Caliber556x45NATO", 53, 42),
+            round("PS", "Caliber762x39", 35, 57),
+            round("LPS", "Caliber762x54R", 42, 81),
+            round("7N1", "Caliber762x54R", 45, 86),
+        ]
+    }
+
+    #[test]
+    fn finds_caliber_by_loose_name() {
+        let ammo = ammo();
+        for query in ["5.56", "556x45", "5.56x45 NATO", "Caliber556x45NATO"] {
+            let names = rounds(&ammo, query)
+                .unwrap()
+                .iter()
+                .map(|round| round.item.name.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["M995", "M855"], "{query}");
+        }
+    }
+
+    #[test]
+    fn ambiguous_caliber_lists_choices() {
+        let ammo = ammo();
+        let error = rounds(&ammo, "7.62").unwrap_err();
+        assert!(error.contains("762x39, 762x54R"));
+        assert_eq!(rounds(&ammo, "762x39").unwrap().len(), 1);
+        assert!(rounds(&ammo, "12g").is_err());
+    }
+
+    #[test]
+    fn rates_against_armor_class() {
+        assert_eq!(Rating::of(53, 4), Rating::Reliable);
+        assert_eq!(Rating::of(45, 4), Rating::Often);
+        assert_eq!(Rating::of(31, 4), Rating::Worn);
+        assert_eq!(Rating::of(20, 5), Rating::Barely);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::storage::Error;
+use crate::tarkov::types::Ammo;
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 500;
+/// Plot area, the rest holds the axis labels.
+const LEFT: usize = 70;
+const RIGHT: usize = WIDTH - 20;
+const TOP: usize = 40;
+const BOTTOM: usize = HEIGHT - 60;
+/// Glyphs are drawn this many pixels per font pixel.
+const SCALE: usize = 2;
+const POINT_RADIUS: i64 = 5;
+
+type Rgb = [u8; 3];
+
+// Discord's dark theme, so the chart sits in it without a frame
+const BACKGROUND: Rgb = [0x2b, 0x2d, 0x31];
+const GRID: Rgb = [0x3f, 0x41, 0x47];
+const CLASS_LINE: Rgb = [0x6d, 0x6f, 0x78];
+const AXIS: Rgb = [0xb5, 0xba, 0xc1];
+const PALETTE: [Rgb; 8] = [
+    [0xed, 0x42, 0x45],
+    [0x57, 0xf2, 0x87],
+    [0x58, 0x65, 0xf2],
+    [0xfe, 0xe7, 0x5c],
+    [0xeb, 0x45, 0x9e],
+    [0x3b, 0xa5, 0x5d],
+    [0xf0, 0x8c, 0x2e],
+    [0x00, 0xb0, 0xf4],
+];
+
+/// Penetration against damage of `rounds` as a PNG, each point numbered
+/// like the table row it belongs to. Armor class thresholds are the lighter
+/// vertical lines.
+pub fn scatter(rounds: &[&Ammo]) -> Result<Vec<u8>, Error> {
+    let max_pen = rounds
+        .iter()
+        .map(|round| round.penetration_power)
+        .max()
+        .unwrap_or(0);
+    // Always reach class 6 so every threshold is on the chart
+    let x_max = round_up(max_pen.max(60) + 1, 10);
+    let max_damage = rounds.iter().map(|round| round.damage).max().unwrap_or(0);
+    let y_step = [10, 20, 25, 50, 100, 200]
+        .into_iter()
+        .find(|step| max_damage / step < 8)
+        .unwrap_or(500);
+    let y_max = round_up(max_damage + 1, y_step);
+
+    let x_at = |pen: u64| LEFT + (pen as usize * (RIGHT - LEFT)) / x_max as usize;
+    let y_at = |damage: u64| BOTTOM - (damage as usize * (BOTTOM - TOP)) / y_max as usize;
+
+    let mut canvas = Canvas::new(BACKGROUND);
+    for pen in (0..=x_max).step_by(10) {
+        let colour = if (10..=60).contains(&pen) {
+            CLASS_LINE
+        } else {
+            GRID
+        };
+        canvas.vertical(x_at(pen), TOP, BOTTOM, colour);
+        let label = pen.to_string();
+        canvas.text(x_at(pen) - text_width(&label) / 2, BOTTOM + 8, &label, AXIS);
+        if (10..=60).contains(&pen) {
+            let class = format!("C{}", pen / 10);
+            canvas.text(x_at(pen) - text_width(&class) / 2, TOP - 20, &class, AXIS);
+        }
+    }
+    for damage in (0..=y_max).step_by(y_step as usize) {
+        canvas.horizontal(LEFT, RIGHT, y_at(damage), GRID);
+        let label = damage.to_string();
+        canvas.text(
+            LEFT - 8 - text_width(&label),
+            y_at(damage) - 7,
+            &label,
+            AXIS,
+        );
+    }
+    canvas.vertical(LEFT, TOP, BOTTOM, AXIS);
+    canvas.horizontal(LEFT, RIGHT, BOTTOM, AXIS);
+    canvas.text(
+        (LEFT + RIGHT - text_width("PENETRATION")) / 2,
+        BOTTOM + 32,
+        "PENETRATION",
+        AXIS,
+    );
+    canvas.text(4, TOP - 36, "DMG", AXIS);
+
+    for (i, round) in rounds.iter().enumerate() {
+        let colour = PALETTE[i % PALETTE.len()];
+        let (x, y) = (x_at(round.penetration_power), y_at(round.damage));
+        canvas.dot(x, y, colour);
+        canvas.text(x + 8, y.saturating_sub(16), &(i + 1).to_string(), colour);
+    }
+    canvas.encode()
+}
+
+fn round_up(value: u64, step: u64) -> u64 {
+    value.div_ceil(step) * step
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 6 * SCALE
+}
+
+/// An RGB image drawn pixel by pixel.
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(background: Rgb) -> Self {
+        Self {
+            pixels: background.repeat(WIDTH * HEIGHT),
+        }
+    }
+
+    /// Sets a pixel, ignoring anything off the canvas.
+    fn set(&mut self, x: i64, y: i64, colour: Rgb) {
+        if !(0..WIDTH as i64).contains(&x) || !(0..HEIGHT as i64).contains(&y) {
+            return;
+        }
+        let offset = (y as usize * WIDTH + x as usize) * 3;
+        self.pixels[offset..offset + 3].copy_from_slice(&colour);
+    }
+
+    fn vertical(&mut self, x: usize, top: usize, bottom: usize, colour: Rgb) {
+        for y in top..=bottom {
+            self.set(x as i64, y as i64, colour);
+        }
+    }
+
+    fn horizontal(&mut self, left: usize, right: usize, y: usize, colour: Rgb) {
+        for x in left..=right {
+            self.set(x as i64, y as i64, colour);
+        }
+    }
+
+    fn dot(&mut self, x: usize, y: usize, colour: Rgb) {
+        let (x, y) = (x as i64, y as i64);
+        for dy in -POINT_RADIUS..=POINT_RADIUS {
+            for dx in -POINT_RADIUS..=POINT_RADIUS {
+                if dx * dx + dy * dy <= POINT_RADIUS * POINT_RADIUS {
+                    self.set(x + dx, y + dy, colour);
+                }
+            }
+        }
+    }
+
+    /// Draws `text` with its top left corner at `x`, `y`. Characters
+    /// without a glyph are left blank.
+    fn text(&mut self, x: usize, y: usize, text: &str, colour: Rgb) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * 6 * SCALE;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) == 0 {
+                        continue;
+                    }
+                    for sy in 0..SCALE {
+                        for sx in 0..SCALE {
+                            self.set(
+                                (left + column * SCALE + sx) as i64,
+                                (y + row * SCALE + sy) as i64,
+                                colour,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, WIDTH as u32, HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(png)
+    }
+}
+
+/// 5x7 glyphs for what the chart writes, one row per byte.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        _ => [0; 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarkov::types::ItemName;
+
+    #[test]
+    fn renders_png() {
+        let round = Ammo {
+            item: ItemName {
+                id: "m995".to_string(),
+                name: "5.56x45mm M995".to_string(),
+                short_name: "M995".to_string(),
+            },
+            caliber: "Caliber556x45NATO".to_string(),
+            damage: 42,
+            armor_damage: 64,
+            penetration_power: 53,
+            recoil: 0.05,
+        };
+        let png = scatter(&[&round]).unwrap();
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().width, WIDTH as u32);
+        assert_eq!(reader.info().height, HEIGHT as u32);
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+
+        // Penetration runs to 70 and damage to 50, which puts 53/42 here
+        let (x, y) = (
+            LEFT + 53 * (RIGHT - LEFT) / 70,
+            BOTTOM - 42 * (BOTTOM - TOP) / 50,
+        );
+        let offset = (y * WIDTH + x) * 3;
+        assert_eq!(pixels[offset..offset + 3], PALETTE[0]);
+        assert_eq!(pixels[..3], BACKGROUND);
+    }
+}
@@ -0,0 +1,597 @@
+use crate::tarkov::types::{
+    Ammo, CraftBook, HideoutStation, Item, ItemName, Quest, QuestNode, Task,
+};
+
+use reqwest::Client as HttpClient;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use tracing::warn;
+
+pub const API_URL: &str = "https://api.tarkov.dev/graphql";
+
+const TASK_QUERY: &str = r#"
+query Task($id: ID) {
+    task(id: $id) {
+        name
+        minPlayerLevel
+        kappaRequired
+        wikiLink
+        neededKeys {
+            keys {
+                name
+                avg24hPrice
+                wikiLink
+            }
+        }
+    }
+}"#;
+
+const TASKS_QUERY: &str = r#"
+query Tasks {
+    tasks {
+        id
+        name
+    }
+}"#;
+
+const QUEST_TREE_QUERY: &str = r#"
+query QuestTree {
+    tasks {
+        id
+        name
+        minPlayerLevel
+        kappaRequired
+        trader {
+            name
+        }
+        taskRequirements {
+            task {
+                id
+            }
+            status
+        }
+        traderRequirements {
+            trader {
+                name
+            }
+            requirementType
+            value
+        }
+        objectives {
+            type
+            ... on TaskObjectiveItem {
+                items {
+                    name
+                }
+                count
+                foundInRaid
+            }
+        }
+    }
+}"#;
+
+const ITEMS_QUERY: &str = r#"
+query Items {
+    items {
+        id
+        name
+        shortName
+    }
+}"#;
+
+const ITEM_QUERY: &str = r#"
+query Item($id: ID) {
+    item(id: $id) {
+        name
+        shortName
+        avg24hPrice
+        lastLowPrice
+        changeLast48hPercent
+        width
+        height
+        iconLink
+        wikiLink
+        sellFor {
+            priceRUB
+            vendor {
+                name
+                normalizedName
+            }
+        }
+        historicalPrices {
+            price
+            timestamp
+        }
+    }
+}"#;
+
+const AMMO_QUERY: &str = r#"
+query Ammo {
+    ammo {
+        item {
+            id
+            name
+            shortName
+        }
+        caliber
+        damage
+        armorDamage
+        penetrationPower
+        recoilModifier
+    }
+}"#;
+
+/// Prices hideout costs and craft profits are worked out from.
+const PRICED_ITEM_FRAGMENT: &str = r#"
+fragment PricedItem on Item {
+    id
+    name
+    avg24hPrice
+    lastLowPrice
+    fleaMarketFee
+    buyFor {
+        priceRUB
+        vendor {
+            name
+            normalizedName
+        }
+    }
+    sellFor {
+        priceRUB
+        vendor {
+            name
+            normalizedName
+        }
+    }
+}"#;
+
+const HIDEOUT_QUERY: &str = r#"
+query Hideout {
+    hideoutStations {
+        id
+        name
+        levels {
+            level
+            constructionTime
+            itemRequirements {
+                count
+                item {
+                    ...PricedItem
+                }
+            }
+            stationLevelRequirements {
+                station {
+                    name
+                }
+                level
+            }
+            traderRequirements {
+                trader {
+                    name
+                }
+                requirementType
+                value
+            }
+            skillRequirements {
+                name
+                level
+            }
+        }
+    }
+}"#;
+
+const CRAFTS_QUERY: &str = r#"
+query Crafts {
+    crafts {
+        id
+        station {
+            name
+        }
+        level
+        duration
+        requiredItems {
+            count
+            item {
+                ...PricedItem
+            }
+            attributes {
+                type
+            }
+        }
+        rewardItems {
+            count
+            item {
+                ...PricedItem
+            }
+        }
+    }
+    fuel: item(normalizedName: "metal-fuel-tank") {
+        ...PricedItem
+    }
+}"#;
+
+/// Why a tarkov.dev query failed.
+#[derive(Debug)]
+pub enum TarkovError {
+    /// The request never got a successful HTTP response.
+    Transport(reqwest::Error),
+    /// The API answered with errors instead of data.
+    GraphQl(Vec<String>),
+    /// The response is not shaped like what we asked for.
+    Schema(serde_json::Error),
+}
+
+impl fmt::Display for TarkovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TarkovError::Transport(e) => write!(f, "could not reach tarkov.dev: {e}"),
+            TarkovError::GraphQl(messages) => {
+                write!(f, "tarkov.dev returned errors: {}", messages.join("; "))
+            }
+            TarkovError::Schema(e) => write!(f, "unexpected response from tarkov.dev: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TarkovError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TarkovError::Transport(e) => Some(e),
+            TarkovError::GraphQl(_) => None,
+            TarkovError::Schema(e) => Some(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for TarkovError {
+    fn from(e: reqwest::Error) -> Self {
+        TarkovError::Transport(e)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<Value>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskData {
+    task: Option<Task>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TasksData {
+    tasks: Vec<Quest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuestTreeData {
+    tasks: Vec<QuestNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemsData {
+    items: Vec<ItemName>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemData {
+    item: Option<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HideoutData {
+    hideout_stations: Vec<HideoutStation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AmmoData {
+    ammo: Vec<Ammo>,
+}
+
+/// GraphQL client for the tarkov.dev API, sharing the bot's HTTP client.
+#[derive(Clone)]
+pub struct TarkovClient {
+    http_client: HttpClient,
+    url: String,
+}
+
+impl TarkovClient {
+    pub fn new(http_client: HttpClient, url: impl Into<String>) -> Self {
+        Self {
+            http_client,
+            url: url.into(),
+        }
+    }
+
+    /// Runs `query` with `variables` and deserializes its `data`. Errors next
+    /// to usable data are only logged, the API reports partial failures that way.
+    pub async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<T, TarkovError> {
+        let body = self
+            .http_client
+            .post(&self.url)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let response: GraphQlResponse =
+            serde_json::from_slice(&body).map_err(TarkovError::Schema)?;
+
+        let messages = response
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect::<Vec<_>>();
+        match response.data {
+            Some(data) if !data.is_null() => {
+                if !messages.is_empty() {
+                    warn!("tarkov.dev returned partial data: {}", messages.join("; "));
+                }
+                serde_json::from_value(data).map_err(TarkovError::Schema)
+            }
+            _ if !messages.is_empty() => Err(TarkovError::GraphQl(messages)),
+            _ => Err(TarkovError::GraphQl(vec!["no data".to_string()])),
+        }
+    }
+
+    /// The quest with `id`, `None` when the API does not know it.
+    pub async fn task(&self, id: &str) -> Result<Option<Task>, TarkovError> {
+        let data: TaskData = self.query(TASK_QUERY, json!({ "id": id })).await?;
+        Ok(data.task)
+    }
+
+    /// Every quest, by name and id.
+    pub async fn tasks(&self) -> Result<Vec<Quest>, TarkovError> {
+        let data: TasksData = self.query(TASKS_QUERY, json!({})).await?;
+        Ok(data.tasks)
+    }
+
+    /// Every quest with its requirements and objectives.
+    pub async fn quest_tree(&self) -> Result<Vec<QuestNode>, TarkovError> {
+        let data: QuestTreeData = self.query(QUEST_TREE_QUERY, json!({})).await?;
+        Ok(data.tasks)
+    }
+
+    /// Every item, by name, short name and id.
+    pub async fn items(&self) -> Result<Vec<ItemName>, TarkovError> {
+        let data: ItemsData = self.query(ITEMS_QUERY, json!({})).await?;
+        Ok(data.items)
+    }
+
+    /// Prices of the item with `id`, `None` when the API does not know it.
+    pub async fn item(&self, id: &str) -> Result<Option<Item>, TarkovError> {
+        let data: ItemData = self.query(ITEM_QUERY, json!({ "id": id })).await?;
+        Ok(data.item)
+    }
+
+    /// Every round of every caliber.
+    pub async fn ammo(&self) -> Result<Vec<Ammo>, TarkovError> {
+        let data: AmmoData = self.query(AMMO_QUERY, json!({})).await?;
+        Ok(data.ammo)
+    }
+
+    /// Every hideout station with what each level costs.
+    pub async fn hideout(&self) -> Result<Vec<HideoutStation>, TarkovError> {
+        let query = format!("{HIDEOUT_QUERY}\n{PRICED_ITEM_FRAGMENT}");
+        let data: HideoutData = self.query(&query, json!({})).await?;
+        Ok(data.hideout_stations)
+    }
+
+    /// Every craft and the price of fuel.
+    pub async fn crafts(&self) -> Result<CraftBook, TarkovError> {
+        let query = format!("{CRAFTS_QUERY}\n{PRICED_ITEM_FRAGMENT}");
+        self.query(&query, json!({})).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Answers each request with the next canned response and keeps the
+    /// request bodies for inspection.
+    pub(crate) struct MockServer {
+        url: String,
+        pub(crate) requests: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl MockServer {
+        pub(crate) fn start(responses: Vec<(u16, &str)>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let received = Arc::clone(&requests);
+            let responses = responses
+                .into_iter()
+                .map(|(status, body)| (status, body.to_string()))
+                .collect::<Vec<_>>();
+
+            thread::spawn(move || {
+                for (status, body) in responses {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut request = vec![0; length];
+                    reader.read_exact(&mut request).unwrap();
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::from_slice(&request).unwrap());
+
+                    let mut stream = reader.into_inner();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+                }
+            });
+            Self { url, requests }
+        }
+
+        pub(crate) fn client(&self) -> TarkovClient {
+            TarkovClient::new(HttpClient::new(), &self.url)
+        }
+    }
+
+    const TASK: &str = r#"{"data":{"task":{
+        "name":"Shooting Cans","minPlayerLevel":5,"kappaRequired":true,
+        "wikiLink":"https://escapefromtarkov.fandom.com/wiki/Shooting_Cans",
+        "neededKeys":[{"keys":[{"name":"Dorm room 114 key","avg24hPrice":25000,"wikiLink":null}]}]
+    }}}"#;
+
+    #[tokio::test]
+    async fn sends_variables_and_parses_task() {
+        let server = MockServer::start(vec![(200, TASK)]);
+        let task = server
+            .client()
+            .task("657315df034d76585f032e01")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(task.name, "Shooting Cans");
+        assert_eq!(task.min_player_level, Some(5));
+        assert!(task.kappa_required);
+        assert_eq!(task.needed_keys[0].keys[0].avg_24h_price, Some(25000));
+
+        let request = server.requests.lock().unwrap().remove(0);
+        assert_eq!(request["variables"]["id"], "657315df034d76585f032e01");
+        let query = request["query"].as_str().unwrap();
+        assert!(query.contains("task(id: $id)"));
+        assert!(!query.contains("657315df034d76585f032e01"));
+    }
+
+    #[tokio::test]
+    async fn parses_item_prices() {
+        let server = MockServer::start(vec![(
+            200,
+            r#"{"data":{"item":{
+                "name":"LEDX Skin Transilluminator","shortName":"LEDX",
+                "avg24hPrice":1100000,"lastLowPrice":1050000,"changeLast48hPercent":-2.5,
+                "width":1,"height":1,"iconLink":null,"wikiLink":null,
+                "sellFor":[
+                    {"priceRUB":1050000,"vendor":{"name":"Flea Market","normalizedName":"flea-market"}},
+                    {"priceRUB":520000,"vendor":{"name":"Therapist","normalizedName":"therapist"}},
+                    {"priceRUB":480000,"vendor":{"name":"Mechanic","normalizedName":"mechanic"}}
+                ],
+                "historicalPrices":[
+                    {"price":1000000,"timestamp":"1000000"},
+                    {"price":1040000,"timestamp":"50000000"},
+                    {"price":1090000,"timestamp":"90000000"}
+                ]
+            }}}"#,
+        )]);
+        let item = server.client().item("ledx").await.unwrap().unwrap();
+
+        let best = item.best_trader_sell().unwrap();
+        assert_eq!(best.vendor.name, "Therapist");
+        assert_eq!(best.price_rub, Some(520_000));
+        // A day before 100,000,000 ms the last recorded average was 1,000,000
+        let change = item.change_last_24h_percent(100_000_000).unwrap();
+        assert!((change - 10.0).abs() < 1e-9);
+        assert_eq!(item.change_last_24h_percent(1000), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_task_is_none() {
+        let server = MockServer::start(vec![(200, r#"{"data":{"task":null}}"#)]);
+        assert!(server.client().task("nope").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn graphql_errors_are_reported() {
+        let server = MockServer::start(vec![(
+            200,
+            r#"{"errors":[{"message":"Variable \"$id\" got invalid value"}],"data":null}"#,
+        )]);
+        match server.client().task("bad").await {
+            Err(TarkovError::GraphQl(messages)) => {
+                assert_eq!(messages, ["Variable \"$id\" got invalid value"])
+            }
+            other => panic!("expected a GraphQL error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn partial_errors_keep_the_data() {
+        let partial = TASK.replacen(
+            r#"{"data""#,
+            r#"{"errors":[{"message":"price unavailable"}],"data""#,
+            1,
+        );
+        let server = MockServer::start(vec![(200, &partial)]);
+        assert!(server.client().task("id").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn mismatched_shape_is_a_schema_error() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                r#"{"data":{"task":{"name":"Debut","kappaRequired":"yes"}}}"#,
+            ),
+            (200, "<html>Bad gateway</html>"),
+        ]);
+        let client = server.client();
+        assert!(matches!(
+            client.task("id").await,
+            Err(TarkovError::Schema(_))
+        ));
+        assert!(matches!(
+            client.task("id").await,
+            Err(TarkovError::Schema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn http_failures_are_transport_errors() {
+        let server = MockServer::start(vec![(502, r#"{"message":"bad gateway"}"#)]);
+        assert!(matches!(
+            server.client().task("id").await,
+            Err(TarkovError::Transport(_))
+        ));
+
+        // Nothing listens on the port once this listener is dropped
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", closed.local_addr().unwrap());
+        drop(closed);
+        assert!(matches!(
+            TarkovClient::new(HttpClient::new(), url).task("id").await,
+            Err(TarkovError::Transport(_))
+        ));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::storage::{Error, JsonStore};
+use crate::tarkov::client::{TarkovClient, TarkovError};
+use crate::tarkov::types::{
+    Ammo, CraftBook, HideoutStation, Item, ItemName, Quest, QuestNode, Task,
+};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const CACHE_DIR: &str = "data/tarkov";
+const QUESTS_PATH: &str = "data/quests.json";
+/// Quests, items and maps change with game patches.
+pub const STATIC_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Flea prices move all day.
+pub const PRICE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A response as it was fetched, kept on disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    fetched_at: u64,
+    value: Value,
+}
+
+/// A response already parsed, kept in memory so hits skip deserializing.
+struct Loaded {
+    fetched_at: u64,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+/// Data with where it came from. Stale data is served when tarkov.dev is
+/// unreachable and the cache has nothing fresher.
+pub struct Cached<T> {
+    pub value: Arc<T>,
+    pub fetched_at: u64,
+    pub stale: bool,
+}
+
+impl<T> Cached<T> {
+    /// Tells the reader how old stale data is, nothing when it is fresh.
+    pub fn note(&self) -> Option<String> {
+        self.stale.then(|| {
+            format!(
+                "⚠️ tarkov.dev is unreachable, showing data as of <t:{}:R>",
+                self.fetched_at
+            )
+        })
+    }
+}
+
+/// tarkov.dev data behind a TTL cache, each query kept as a file under
+/// `data/tarkov/` so restarts and outages still have something to show.
+#[derive(Clone)]
+pub struct TarkovData {
+    client: TarkovClient,
+    dir: PathBuf,
+    memory: Arc<Mutex<HashMap<String, Loaded>>>,
+    // One store per key, so concurrent misses on a key share its lock
+    stores: Arc<Mutex<HashMap<String, JsonStore<Option<Entry>>>>>,
+    quest_file: JsonStore<Vec<Quest>>,
+    // Parsed quest file, read once and replaced on refresh
+    quests: Arc<Mutex<Option<Arc<Vec<Quest>>>>>,
+}
+
+impl TarkovData {
+    pub fn new(client: TarkovClient) -> Self {
+        Self::with_dir(client, CACHE_DIR)
+    }
+
+    /// Like `new`, with the cached responses kept under `dir`.
+    pub fn with_dir(client: TarkovClient, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            dir: dir.into(),
+            memory: Arc::new(Mutex::new(HashMap::new())),
+            stores: Arc::new(Mutex::new(HashMap::new())),
+            quest_file: JsonStore::new(QUESTS_PATH),
+            quests: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Quest names and ids from `data/quests.json`.
+    pub fn quests(&self) -> Result<Arc<Vec<Quest>>, Error> {
+        let mut quests = self.quests.lock().unwrap();
+        if let Some(quests) = &*quests {
+            return Ok(Arc::clone(quests));
+        }
+        let loaded = Arc::new(self.quest_file.load()?);
+        *quests = Some(Arc::clone(&loaded));
+        Ok(loaded)
+    }
+
+    /// Rewrites `data/quests.json` from the API, returning how many quests
+    /// there are now.
+    pub async fn refresh_quests(&self) -> Result<usize, Error> {
+        let mut quests = self.client.tasks().await?;
+        quests.sort_by(|a, b| a.name.cmp(&b.name));
+        self.quest_file
+            .update(|stored| stored.clone_from(&quests))?;
+
+        let count = quests.len();
+        *self.quests.lock().unwrap() = Some(Arc::new(quests));
+        Ok(count)
+    }
+
+    /// The quest with `id`. Its key prices are 24 hour averages, a day old
+    /// is close enough for them.
+    pub async fn task(&self, id: &str) -> Result<Cached<Option<Task>>, Error> {
+        let id = checked_id(id)?;
+        self.cached(&format!("task-{id}"), STATIC_TTL, || self.client.task(id))
+            .await
+    }
+
+    /// Every quest with what unlocks it, for progress tracking.
+    pub async fn quest_tree(&self) -> Result<Cached<Vec<QuestNode>>, Error> {
+        self.cached("quest-tree", STATIC_TTL, || self.client.quest_tree())
+            .await
+    }
+
+    /// Names and ids of every item, to search prices by.
+    pub async fn items(&self) -> Result<Cached<Vec<ItemName>>, Error> {
+        self.cached("items", STATIC_TTL, || self.client.items())
+            .await
+    }
+
+    /// Prices of the item with `id`.
+    pub async fn item(&self, id: &str) -> Result<Cached<Option<Item>>, Error> {
+        let id = checked_id(id)?;
+        self.cached(&format!("item-{id}"), PRICE_TTL, || self.client.item(id))
+            .await
+    }
+
+    /// Every round of every caliber.
+    pub async fn ammo(&self) -> Result<Cached<Vec<Ammo>>, Error> {
+        self.cached("ammo", STATIC_TTL, || self.client.ammo()).await
+    }
+
+    /// Hideout stations, their costs priced like the flea market.
+    pub async fn hideout(&self) -> Result<Cached<Vec<HideoutStation>>, Error> {
+        self.cached("hideout", PRICE_TTL, || self.client.hideout())
+            .await
+    }
+
+    /// Crafts with their input and output prices.
+    pub async fn crafts(&self) -> Result<Cached<CraftBook>, Error> {
+        self.cached("crafts", PRICE_TTL, || self.client.crafts())
+            .await
+    }
+
+    /// The cached value under `key` while younger than `ttl`, otherwise a
+    /// fresh one from `fetch`, or the old one when the API is unreachable.
+    async fn cached<T, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        fetch: F,
+    ) -> Result<Cached<T>, Error>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, TarkovError>>,
+    {
+        let entry = self.entry::<T>(key);
+        if let Some((fetched_at, value)) = &entry {
+            if now().saturating_sub(*fetched_at) < ttl.as_secs() {
+                return Ok(Cached {
+                    value: Arc::clone(value),
+                    fetched_at: *fetched_at,
+                    stale: false,
+                });
+            }
+        }
+
+        match fetch().await {
+            Ok(value) => {
+                let fetched_at = now();
+                let stored = Entry {
+                    fetched_at,
+                    value: serde_json::to_value(&value)?,
+                };
+                if let Err(e) = self.store(key).update(|entry| *entry = Some(stored)) {
+                    warn!("Could not save tarkov.dev data for {key}: {e}");
+                }
+                let value = Arc::new(value);
+                self.remember(key, fetched_at, Arc::clone(&value));
+                Ok(Cached {
+                    value,
+                    fetched_at,
+                    stale: false,
+                })
+            }
+            Err(TarkovError::Transport(e)) => {
+                let Some((fetched_at, value)) = entry else {
+                    return Err(TarkovError::Transport(e).into());
+                };
+                warn!("tarkov.dev unreachable, serving {key} from the cache: {e}");
+                Ok(Cached {
+                    value,
+                    fetched_at,
+                    stale: true,
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// What the cache has for `key`, from memory or else parsed from disk.
+    fn entry<T>(&self, key: &str) -> Option<(u64, Arc<T>)>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        if let Some(loaded) = self.memory.lock().unwrap().get(key) {
+            if let Ok(value) = Arc::clone(&loaded.value).downcast::<T>() {
+                return Some((loaded.fetched_at, value));
+            }
+        }
+        let entry = self.store(key).load().unwrap_or_else(|e| {
+            warn!("Could not read cached tarkov.dev data for {key}: {e}");
+            None
+        })?;
+        let value = match serde_json::from_value(entry.value) {
+            Ok(value) => Arc::new(value),
+            Err(e) => {
+                warn!("Cached tarkov.dev data for {key} no longer parses: {e}");
+                return None;
+            }
+        };
+        self.remember(key, entry.fetched_at, Arc::clone(&value));
+        Some((entry.fetched_at, value))
+    }
+
+    fn remember<T: Send + Sync + 'static>(&self, key: &str, fetched_at: u64, value: Arc<T>) {
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Loaded { fetched_at, value });
+    }
+
+    fn store(&self, key: &str) -> JsonStore<Option<Entry>> {
+        self.stores
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_insert_with(|| JsonStore::new(self.dir.join(format!("{key}.json"))))
+            .clone()
+    }
+}
+
+/// `id` if it is safe in a file name. Menu picks carry ids the client sent,
+/// and tarkov.dev ids are plain alphanumeric.
+fn checked_id(id: &str) -> Result<&str, Error> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("That is not a tarkov.dev id".into());
+    }
+    Ok(id)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarkov::client::tests::MockServer;
+    use serde_json::json;
+    use std::env;
+    use std::fs;
+
+    const ITEMS: &str = r#"{"data":{"items":[{"id":"salewa","name":"Salewa first aid kit","shortName":"Salewa"}]}}"#;
+
+    fn data(name: &str, server: &MockServer) -> TarkovData {
+        let dir = env::temp_dir().join(format!("mee6-tarkov-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        TarkovData::with_dir(server.client(), dir)
+    }
+
+    /// Leaves items fetched `age` ago on disk, as an earlier run would.
+    fn seed(data: &TarkovData, age: Duration) {
+        let entry = Entry {
+            fetched_at: now() - age.as_secs(),
+            value: json!([{ "id": "old", "name": "Old item", "shortName": "Old" }]),
+        };
+        data.store("items")
+            .update(|stored| *stored = Some(entry))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fresh_data_skips_the_api() {
+        let server = MockServer::start(Vec::new());
+        let data = data("fresh", &server);
+        seed(&data, Duration::ZERO);
+
+        let items = data.items().await.unwrap();
+        assert!(!items.stale);
+        assert_eq!(items.value[0].id, "old");
+        // The second lookup reuses what the first one parsed
+        let again = data.items().await.unwrap();
+        assert!(Arc::ptr_eq(&items.value, &again.value));
+        assert!(server.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_data_is_fetched_again() {
+        let server = MockServer::start(vec![(200, ITEMS)]);
+        let data = data("expired", &server);
+        seed(&data, STATIC_TTL);
+
+        let items = data.items().await.unwrap();
+        assert!(items.note().is_none());
+        assert_eq!(items.value[0].id, "salewa");
+        assert_eq!(server.requests.lock().unwrap().len(), 1);
+        let stored = data.store("items").load().unwrap().unwrap();
+        assert_eq!(stored.value[0]["id"], "salewa");
+    }
+
+    #[tokio::test]
+    async fn unreachable_api_serves_stale_data() {
+        let server = MockServer::start(Vec::new());
+        let data = data("stale", &server);
+        // Nothing cached yet to fall back on
+        assert!(data.items().await.is_err());
+
+        seed(&data, STATIC_TTL);
+        let items = data.items().await.unwrap();
+        assert!(items.stale);
+        assert_eq!(items.value[0].id, "old");
+        let note = items.note().unwrap();
+        assert!(note.contains(&format!("<t:{}:R>", items.fetched_at)));
+    }
+
+    #[tokio::test]
+    async fn api_errors_do_not_fall_back() {
+        let server = MockServer::start(vec![
+            (200, r#"{"errors":[{"message":"Syntax Error"}]}"#),
+            (200, r#"{"data":{"items":"none"}}"#),
+        ]);
+        let data = data("errors", &server);
+        seed(&data, STATIC_TTL);
+
+        let error = data.items().await.err().unwrap();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(TarkovError::GraphQl(_))
+        ));
+        let error = data.items().await.err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(TarkovError::Schema(_))));
+    }
+
+    #[tokio::test]
+    async fn refuses_ids_unfit_for_file_names() {
+        let server = MockServer::start(Vec::new());
+        let data = data("ids", &server);
+        for id in ["../../settings", "a/b", ""] {
+            assert!(data.task(id).await.is_err(), "{id}");
+            assert!(data.item(id).await.is_err(), "{id}");
+        }
+        assert!(server.requests.lock().unwrap().is_empty());
+        assert!(!data.dir.exists());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::tarkov::search;
+use crate::tarkov::types::{ContainedItem, Craft, CraftBook, HideoutStation, StationLevel};
+
+use std::collections::BTreeMap;
+
+/// How long a metal fuel tank keeps the generator running, which crafts
+/// need to progress.
+const FUEL_TANK_HOURS: f64 = 14.5;
+
+/// What a craft costs and earns, in roubles.
+#[derive(Debug)]
+pub struct CraftProfit<'a> {
+    pub craft: &'a Craft,
+    pub cost: i64,
+    pub revenue: i64,
+    pub fuel: i64,
+    pub profit: i64,
+    pub per_hour: i64,
+}
+
+impl<'a> CraftProfit<'a> {
+    /// `None` when an input or output has no price to go by.
+    pub fn of(craft: &'a Craft, fuel_per_hour: f64) -> Option<Self> {
+        if craft.duration == 0 {
+            return None;
+        }
+        let cost = total(
+            craft.required_items.iter().filter(|input| !input.is_tool()),
+            |input| input.item.buy_price(),
+        )?;
+        let revenue = total(&craft.reward_items, |output| output.item.sell_value())?;
+        let hours = craft.duration as f64 / 3600.0;
+        let fuel = (fuel_per_hour * hours).round() as i64;
+        let profit = revenue - cost - fuel;
+        Some(Self {
+            craft,
+            cost,
+            revenue,
+            fuel,
+            profit,
+            per_hour: (profit as f64 / hours).round() as i64,
+        })
+    }
+}
+
+/// The sum of `price` times count over `items`, `None` if any is unpriced.
+fn total<'a>(
+    items: impl IntoIterator<Item = &'a ContainedItem>,
+    price: impl Fn(&ContainedItem) -> Option<i64>,
+) -> Option<i64> {
+    items.into_iter().try_fold(0, |sum, item| {
+        Some(sum + (price(item)? as f64 * item.count).round() as i64)
+    })
+}
+
+/// Crafts that make money, best per hour first. Stations in `levels` only
+/// offer the crafts of the levels built, the others offer all of them.
+pub fn profitable<'a>(book: &'a CraftBook, levels: &BTreeMap<String, u32>) -> Vec<CraftProfit<'a>> {
+    let fuel_per_hour = book
+        .fuel
+        .as_ref()
+        .and_then(|fuel| fuel.buy_price())
+        .map_or(0.0, |price| price as f64 / FUEL_TANK_HOURS);
+    let mut profits = book
+        .crafts
+        .iter()
+        .filter(|craft| {
+            levels
+                .get(&craft.station.name)
+                .is_none_or(|built| craft.level <= *built)
+        })
+        .filter_map(|craft| CraftProfit::of(craft, fuel_per_hour))
+        .filter(|profit| profit.profit > 0)
+        .collect::<Vec<_>>();
+    profits.sort_by_key(|profit| std::cmp::Reverse(profit.per_hour));
+    profits
+}
+
+/// The station `name` clearly refers to.
+pub fn find_station<'a>(
+    stations: &'a [HideoutStation],
+    name: &str,
+) -> Result<&'a HideoutStation, String> {
+    let matches = search::rank(stations, name, |station| [station.name.as_str()]);
+    if let Some(found) = search::best(&matches) {
+        return Ok(found.item);
+    }
+    if matches.is_empty() {
+        let names = stations
+            .iter()
+            .map(|station| station.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!("No station called that, there are {names}"));
+    }
+    let closest = matches
+        .iter()
+        .map(|found| found.item.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(format!("Which station? {closest}"))
+}
+
+/// `level` of `station`, or the one after `built` when no level is given.
+pub fn station_level(
+    station: &HideoutStation,
+    level: Option<u32>,
+    built: Option<u32>,
+) -> Result<&StationLevel, String> {
+    let max = station
+        .levels
+        .iter()
+        .map(|level| level.level)
+        .max()
+        .unwrap_or(0);
+    let wanted = match (level, built) {
+        (Some(level), _) => level,
+        (None, Some(built)) if built >= max => {
+            return Err(format!("{} is fully upgraded", station.name));
+        }
+        (None, built) => built.map_or(1, |built| built + 1),
+    };
+    station
+        .levels
+        .iter()
+        .find(|candidate| candidate.level == wanted)
+        .ok_or_else(|| format!("{} has levels 1 to {max}", station.name))
+}
+
+/// Splits "booze generator 1" into the station and the trailing level.
+pub fn split_level(query: &str) -> (&str, Option<u32>) {
+    let query = query.trim();
+    match query.rsplit_once(' ') {
+        Some((station, level)) => match level.parse() {
+            Ok(level) => (station.trim(), Some(level)),
+            Err(_) => (query, None),
+        },
+        None => (query, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarkov::types::{ItemAttribute, ItemPrice, PricedItem, StationRef, Vendor};
+
+    fn offer(vendor: &str, price: i64) -> ItemPrice {
+        ItemPrice {
+            price_rub: Some(price),
+            vendor: Vendor {
+                name: vendor.to_string(),
+                normalized_name: vendor.to_lowercase().replace(' ', "-"),
+            },
+        }
+    }
+
+    fn item(name: &str, buy: i64, flea: i64, fee: i64, trader: i64) -> PricedItem {
+        PricedItem {
+            id: name.to_string(),
+            name: name.to_string(),
+            avg_24h_price: Some(flea),
+            last_low_price: Some(flea),
+            flea_market_fee: Some(fee),
+            buy_for: vec![offer("Flea Market", buy)],
+            sell_for: vec![offer("Flea Market", flea), offer("Therapist", trader)],
+        }
+    }
+
+    fn contained(item: PricedItem, count: f64, tool: bool) -> ContainedItem {
+        ContainedItem {
+            item,
+            count,
+            attributes: if tool {
+                vec![ItemAttribute {
+                    kind: "tool".to_string(),
+                }]
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    fn craft(
+        station: &str,
+        level: u32,
+        hours: u64,
+        inputs: Vec<ContainedItem>,
+        output: ContainedItem,
+    ) -> Craft {
+        Craft {
+            id: format!("{station}-{level}"),
+            station: StationRef {
+                name: station.to_string(),
+            },
+            level,
+            duration: hours * 3600,
+            required_items: inputs,
+            reward_items: vec![output],
+        }
+    }
+
+    fn book() -> CraftBook {
+        let sugar = item("Sugar", 20_000, 20_000, 1_000, 5_000);
+        let moonshine = item("Moonshine", 0, 130_000, 10_000, 40_000);
+        let screwdriver = item("Screwdriver", 15_000, 15_000, 500, 3_000);
+        let bolts = item("Bolts", 10_000, 10_000, 500, 2_000);
+        CraftBook {
+            crafts: vec![
+                craft(
+                    "Booze generator",
+                    1,
+                    3,
+                    vec![contained(sugar.clone(), 2.0, false)],
+                    contained(moonshine, 1.0, false),
+                ),
+                craft(
+                    "Workbench",
+                    2,
+                    1,
+                    vec![
+                        contained(screwdriver, 1.0, true),
+                        contained(sugar, 1.0, false),
+                    ],
+                    contained(bolts, 1.0, false),
+                ),
+            ],
+            fuel: Some(item("Metal fuel tank", 29_000, 29_000, 1_000, 5_000)),
+        }
+    }
+
+    #[test]
+    fn profit_counts_fees_and_fuel() {
+        let book = book();
+        let profits = profitable(&book, &BTreeMap::new());
+        // The workbench craft loses money, tools are not consumed
+        assert_eq!(profits.len(), 1);
+        let moonshine = &profits[0];
+        assert_eq!(moonshine.cost, 40_000);
+        assert_eq!(moonshine.revenue, 120_000);
+        assert_eq!(moonshine.fuel, 6_000);
+        assert_eq!(moonshine.profit, 74_000);
+        assert_eq!(moonshine.per_hour, 24_667);
+
+        let workbench = CraftProfit::of(&book.crafts[1], 0.0).unwrap();
+        assert_eq!(workbench.cost, 20_000);
+    }
+
+    #[test]
+    fn filters_by_built_levels() {
+        let book = book();
+        let levels = BTreeMap::from([("Booze generator".to_string(), 0)]);
+        assert!(profitable(&book, &levels).is_empty());
+    }
+
+    #[test]
+    fn splits_trailing_level() {
+        assert_eq!(
+            split_level("booze generator 1"),
+            ("booze generator", Some(1))
+        );
+        assert_eq!(split_level("medstation"), ("medstation", None));
+        assert_eq!(split_level(" lavatory x "), ("lavatory x", None));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::storage::{Error, JsonStore};
+use crate::tarkov::types::QuestNode;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+const PROGRESS_PATH: &str = "data/quest_progress.json";
+/// The quest that hands out Kappa, it is not marked as required for itself.
+const COLLECTOR: &str = "Collector";
+/// Loyalty level every trader starts at.
+const STARTING_LOYALTY: u32 = 1;
+pub const MAX_LOYALTY_LEVEL: u32 = 4;
+pub const MAX_PLAYER_LEVEL: u32 = 79;
+
+/// What one player has told the bot about their account.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+    /// Ids of completed quests.
+    pub done: BTreeSet<String>,
+    pub level: Option<u32>,
+    /// Loyalty level by trader name.
+    pub traders: BTreeMap<String, u32>,
+    /// Built level by hideout station name.
+    #[serde(default)]
+    pub hideout: BTreeMap<String, u32>,
+}
+
+impl Progress {
+    /// Whether a requirement on quest `id` being in one of `status` is met.
+    /// Only completion is tracked, a quest that just has to be started or
+    /// failed never blocks.
+    fn meets(&self, id: &str, status: &[String]) -> bool {
+        self.done.contains(id) || !status.iter().any(|status| status == "complete")
+    }
+
+    /// Whether `quest` can be taken now, not counting the ones already done.
+    pub fn unlocks(&self, quest: &QuestNode) -> bool {
+        if self.done.contains(&quest.id) {
+            return false;
+        }
+        let level = quest.min_player_level.unwrap_or(0);
+        if self.level.is_some_and(|player| player < level) {
+            return false;
+        }
+        let loyalty = quest
+            .trader_requirements
+            .iter()
+            .filter(|requirement| requirement.requirement_type == "level")
+            .all(|requirement| {
+                let current = self
+                    .traders
+                    .get(&requirement.trader.name)
+                    .copied()
+                    .unwrap_or(STARTING_LOYALTY);
+                current >= requirement.value
+            });
+        loyalty
+            && quest
+                .task_requirements
+                .iter()
+                .all(|requirement| self.meets(&requirement.task.id, &requirement.status))
+    }
+}
+
+/// Per user quest progress, kept in `data/quest_progress.json`.
+#[derive(Clone)]
+pub struct QuestProgress {
+    store: JsonStore<HashMap<u64, Progress>>,
+}
+
+impl QuestProgress {
+    pub fn new() -> Self {
+        Self {
+            store: JsonStore::new(PROGRESS_PATH),
+        }
+    }
+
+    pub fn get(&self, user_id: UserId) -> Result<Progress, Error> {
+        Ok(self
+            .store
+            .load()?
+            .remove(&user_id.get())
+            .unwrap_or_default())
+    }
+
+    pub fn update<R>(
+        &self,
+        user_id: UserId,
+        f: impl FnOnce(&mut Progress) -> R,
+    ) -> Result<R, Error> {
+        self.store
+            .update(|users| f(users.entry(user_id.get()).or_default()))
+    }
+}
+
+/// `id` and every quest that has to be completed before it, as completing
+/// a quest implies.
+pub fn with_prerequisites(quests: &[QuestNode], id: &str) -> BTreeSet<String> {
+    let by_id = quests
+        .iter()
+        .map(|quest| (quest.id.as_str(), quest))
+        .collect::<HashMap<_, _>>();
+    let mut found = BTreeSet::new();
+    let mut pending = vec![id];
+    while let Some(id) = pending.pop() {
+        if !found.insert(id.to_string()) {
+            continue;
+        }
+        let Some(quest) = by_id.get(id) else {
+            continue;
+        };
+        pending.extend(
+            quest
+                .task_requirements
+                .iter()
+                .filter(|requirement| requirement.status.iter().any(|status| status == "complete"))
+                .map(|requirement| requirement.task.id.as_str()),
+        );
+    }
+    found
+}
+
+/// Quests `progress` can take now, by trader and then level.
+pub fn unlocked<'a>(quests: &'a [QuestNode], progress: &Progress) -> Vec<&'a QuestNode> {
+    let mut unlocked = quests
+        .iter()
+        .filter(|quest| progress.unlocks(quest))
+        .collect::<Vec<_>>();
+    unlocked.sort_by(|a, b| {
+        a.trader
+            .name
+            .cmp(&b.trader.name)
+            .then(a.min_player_level.cmp(&b.min_player_level))
+            .then(a.name.cmp(&b.name))
+    });
+    unlocked
+}
+
+/// How far a player is from the Kappa container.
+pub struct Kappa<'a> {
+    pub done: usize,
+    pub total: usize,
+    pub remaining: Vec<&'a QuestNode>,
+    /// Found in raid items the remaining quests still want handed over,
+    /// with how many.
+    pub items: Vec<(String, u32)>,
+}
+
+pub fn kappa<'a>(quests: &'a [QuestNode], progress: &Progress) -> Kappa<'a> {
+    let required = quests
+        .iter()
+        .filter(|quest| quest.kappa_required || quest.name == COLLECTOR)
+        .collect::<Vec<_>>();
+    let remaining = required
+        .iter()
+        .copied()
+        .filter(|quest| !progress.done.contains(&quest.id))
+        .collect::<Vec<_>>();
+
+    let mut items = BTreeMap::<String, u32>::new();
+    for objective in remaining.iter().flat_map(|quest| &quest.objectives) {
+        if objective.kind != "giveItem" || !objective.found_in_raid {
+            continue;
+        }
+        // Objectives listing several items take any one of them
+        let name = objective
+            .items
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" or ");
+        if !name.is_empty() {
+            *items.entry(name).or_default() += objective.count.max(1);
+        }
+    }
+
+    Kappa {
+        done: required.len() - remaining.len(),
+        total: required.len(),
+        remaining,
+        items: items.into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarkov::types::{
+        Objective, ObjectiveItem, TaskRef, TaskRequirement, Trader, TraderRequirement,
+    };
+
+    fn quest(id: &str, trader: &str, level: u32, after: &[&str]) -> QuestNode {
+        QuestNode {
+            id: id.to_string(),
+            name: id.to_string(),
+            min_player_level: Some(level),
+            kappa_required: true,
+            trader: Trader {
+                name: trader.to_string(),
+            },
+            task_requirements: after
+                .iter()
+                .map(|id| TaskRequirement {
+                    task: TaskRef { id: id.to_string() },
+                    status: vec!["complete".to_string()],
+                })
+                .collect(),
+            trader_requirements: Vec::new(),
+            objectives: Vec::new(),
+        }
+    }
+
+    fn hand_in(name: &str, count: u32) -> Objective {
+        Objective {
+            kind: "giveItem".to_string(),
+            items: vec![ObjectiveItem {
+                name: name.to_string(),
+            }],
+            count,
+            found_in_raid: true,
+        }
+    }
+
+    /// Debut leads to Checking and on to Shootout Picnic, which needs
+    /// Prapor at loyalty 2.
+    fn tree() -> Vec<QuestNode> {
+        let mut picnic = quest("Shootout Picnic", "Prapor", 10, &["Checking"]);
+        picnic.trader_requirements.push(TraderRequirement {
+            trader: Trader {
+                name: "Prapor".to_string(),
+            },
+            requirement_type: "level".to_string(),
+            value: 2,
+        });
+        let mut debut = quest("Debut", "Prapor", 1, &[]);
+        debut.objectives.push(hand_in("Salewa", 2));
+        let mut collector = quest("Collector", "Fence", 1, &["Debut"]);
+        collector.kappa_required = false;
+        collector.objectives.push(hand_in("Old firesteel", 1));
+        collector.objectives.push(hand_in("Salewa", 1));
+        vec![
+            debut,
+            quest("Checking", "Prapor", 2, &["Debut"]),
+            picnic,
+            collector,
+        ]
+    }
+
+    fn names(quests: &[&QuestNode]) -> Vec<String> {
+        quests.iter().map(|quest| quest.name.clone()).collect()
+    }
+
+    #[test]
+    fn unlocks_after_prerequisites() {
+        let tree = tree();
+        let mut progress = Progress::default();
+        assert_eq!(names(&unlocked(&tree, &progress)), ["Debut"]);
+
+        progress.done.insert("Debut".to_string());
+        assert_eq!(
+            names(&unlocked(&tree, &progress)),
+            ["Collector", "Checking"]
+        );
+
+        progress.level = Some(1);
+        assert_eq!(names(&unlocked(&tree, &progress)), ["Collector"]);
+    }
+
+    #[test]
+    fn trader_loyalty_gates_quests() {
+        let tree = tree();
+        let mut progress = Progress {
+            done: with_prerequisites(&tree, "Checking"),
+            ..Progress::default()
+        };
+        assert!(unlocked(&tree, &progress)
+            .iter()
+            .all(|quest| quest.name != "Shootout Picnic"));
+
+        progress.traders.insert("Prapor".to_string(), 2);
+        assert!(unlocked(&tree, &progress)
+            .iter()
+            .any(|quest| quest.name == "Shootout Picnic"));
+    }
+
+    #[test]
+    fn completing_implies_prerequisites() {
+        let done = with_prerequisites(&tree(), "Shootout Picnic");
+        assert_eq!(
+            done.into_iter().collect::<Vec<_>>(),
+            ["Checking", "Debut", "Shootout Picnic"]
+        );
+    }
+
+    #[test]
+    fn kappa_counts_remaining_items() {
+        let tree = tree();
+        let mut progress = Progress::default();
+        let kappa_progress = kappa(&tree, &progress);
+        assert_eq!((kappa_progress.done, kappa_progress.total), (0, 4));
+        assert_eq!(
+            kappa_progress.items,
+            [("Old firesteel".to_string(), 1), ("Salewa".to_string(), 3)]
+        );
+
+        progress.done.insert("Debut".to_string());
+        let kappa_progress = kappa(&tree, &progress);
+        assert_eq!(kappa_progress.done, 1);
+        assert_eq!(kappa_progress.items[1], ("Salewa".to_string(), 1));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::text::edit_distance;
+
+/// Candidates scoring below this are not worth suggesting.
+pub const MIN_SCORE: f32 = 0.6;
+/// A top candidate this far ahead of the next one is taken without asking.
+pub const CLEAR_LEAD: f32 = 0.15;
+
+/// A candidate with how well it matched, from 0 to 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Match<'a, T> {
+    pub item: &'a T,
+    pub score: f32,
+}
+
+/// Ranks `items` against `query`, best first, dropping poor matches. `names`
+/// gives every name an item is known by, the best of them counts.
+pub fn rank<'a, T, I, S>(
+    items: &'a [T],
+    query: &str,
+    names: impl Fn(&'a T) -> I,
+) -> Vec<Match<'a, T>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let query = fold(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut matches = items
+        .iter()
+        .filter_map(|item| {
+            let score = names(item)
+                .into_iter()
+                .map(|name| score(&query, &fold(name.as_ref())))
+                .fold(0.0, f32::max);
+            (score >= MIN_SCORE).then_some(Match { item, score })
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches
+}
+
+/// The match to use without asking, when it is exact or clearly ahead.
+pub fn best<'a, 'b, T>(matches: &'b [Match<'a, T>]) -> Option<&'b Match<'a, T>> {
+    match matches {
+        [only] => Some(only),
+        [first, second, ..] if first.score >= 1.0 || first.score - second.score >= CLEAR_LEAD => {
+            Some(first)
+        }
+        _ => None,
+    }
+}
+
+/// How well the folded `query` names the folded `candidate`.
+fn score(query: &str, candidate: &str) -> f32 {
+    if candidate.is_empty() {
+        return 0.0;
+    }
+    if query == candidate {
+        return 1.0;
+    }
+
+    let query_tokens = query.split(' ').collect::<Vec<_>>();
+    let candidate_tokens = candidate.split(' ').collect::<Vec<_>>();
+    let mut used = vec![false; candidate_tokens.len()];
+    let mut total = 0.0;
+    for token in &query_tokens {
+        let best = candidate_tokens
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| (i, token_similarity(token, candidate)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, similarity)) = best {
+            used[i] = true;
+            total += similarity;
+        }
+    }
+    // Names with many words the query never mentions rank a little lower
+    let coverage = used.iter().filter(|used| **used).count() as f32 / used.len() as f32;
+    let tokens = total / query_tokens.len() as f32 * (0.9 + 0.1 * coverage);
+
+    let whole = similarity(query, candidate);
+    let contained = if candidate.contains(query) {
+        0.85 + 0.1 * query.len() as f32 / candidate.len() as f32
+    } else {
+        0.0
+    };
+    // Never claim an exact match that isn't one
+    tokens.max(whole).max(contained).min(0.99)
+}
+
+/// Similarity of two words, where typing the start of a word counts.
+fn token_similarity(query: &str, candidate: &str) -> f32 {
+    if query == candidate {
+        return 1.0;
+    }
+    let prefix = if query.len() >= 2 && candidate.starts_with(query) {
+        0.9
+    } else {
+        0.0
+    };
+    similarity(query, candidate).max(prefix)
+}
+
+fn similarity(a: &str, b: &str) -> f32 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f32 / longest as f32
+}
+
+/// Lowercase words without accents or punctuation, so "Saving the Mole",
+/// "saving-the-mole" and "sávíng thé mole" compare equal. Apostrophes join
+/// rather than split, "Scav's" is "scavs".
+pub fn fold(text: &str) -> String {
+    let folded = text
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .flat_map(char::to_lowercase)
+        .map(|c| match strip_accent(c) {
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect::<String>();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ě' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => 'o',
+        'ř' => 'r',
+        'ś' | 'š' => 's',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUESTS: [&str; 5] = [
+        "Shooting Cans",
+        "Shootout Picnic",
+        "Saving the Mole",
+        "Scav's Stash",
+        "Debut",
+    ];
+
+    fn ranked<'a>(items: &'a [&'a str], query: &str) -> Vec<(&'a str, f32)> {
+        rank(items, query, |item| [*item])
+            .iter()
+            .map(|found| (*found.item, found.score))
+            .collect()
+    }
+
+    fn scored<'a>(scores: &[f32], items: &'a [&'a str]) -> Vec<Match<'a, &'a str>> {
+        scores
+            .iter()
+            .zip(items)
+            .map(|(score, item)| Match {
+                item,
+                score: *score,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_through_typos() {
+        let found = ranked(&QUESTS, "shootin cans");
+        assert_eq!(found[0].0, "Shooting Cans");
+        let matches = rank(&QUESTS, "shootin cans", |item| [*item]);
+        assert_eq!(
+            best(&matches).map(|found| *found.item),
+            Some("Shooting Cans")
+        );
+    }
+
+    #[test]
+    fn folds_accents_and_punctuation() {
+        assert_eq!(fold("Sávíng-thé  Mole!"), "saving the mole");
+        assert_eq!(fold("Scav’s Stash"), "scavs stash");
+        assert_eq!(fold(" ... "), "");
+        assert_eq!(
+            ranked(&QUESTS, "SAVING THE MOLE"),
+            [("Saving the Mole", 1.0)]
+        );
+        assert_eq!(ranked(&QUESTS, "scavs stash")[0], ("Scav's Stash", 1.0));
+        assert!(ranked(&QUESTS, "?!").is_empty());
+    }
+
+    #[test]
+    fn strips_accents() {
+        assert_eq!(strip_accent('é'), 'e');
+        assert_eq!(strip_accent('ř'), 'r');
+        assert_eq!(strip_accent('ů'), 'u');
+        assert_eq!(strip_accent('x'), 'x');
+        assert_eq!(strip_accent('ß'), 'ß');
+    }
+
+    #[test]
+    fn ranks_best_first() {
+        let found = ranked(&QUESTS, "shoot");
+        assert!(found.len() >= 2);
+        assert!(found.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert!(found.iter().all(|(_, score)| *score >= MIN_SCORE));
+        // Both start with it, so neither is clearly meant
+        let matches = rank(&QUESTS, "shoot", |item| [*item]);
+        assert!(best(&matches).is_none());
+    }
+
+    #[test]
+    fn takes_clear_leader_only() {
+        let items = ["a", "b"];
+        assert!(best(&scored(&[], &items)).is_none());
+        assert!(best(&scored(&[0.7], &items)).is_some());
+        assert!(best(&scored(&[0.9, 0.9 - CLEAR_LEAD / 2.0], &items)).is_none());
+        assert!(best(&scored(&[0.9, 0.9 - CLEAR_LEAD * 1.5], &items)).is_some());
+        assert!(best(&scored(&[1.0, 0.99], &items)).is_some());
+    }
+}
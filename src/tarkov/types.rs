@@ -1,45 +1,313 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
-pub struct TaskResponse {
-    pub data: TaskData,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct TaskData {
-    pub task: Task,
-}
-
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Task {
     pub name: String,
-    pub kappaRequired: bool,
-    pub wikiLink: String,
-    pub neededKeys: Vec<NeededKeysWrapper>,
+    pub min_player_level: Option<u32>,
+    pub kappa_required: bool,
+    pub wiki_link: String,
+    pub needed_keys: Vec<NeededKeysWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NeededKeysWrapper {
     pub keys: Vec<Key>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Key {
     pub name: String,
-    pub avg24hPrice: Option<i64>,
-    pub wikiLink: Option<String>,
+    pub avg_24h_price: Option<i64>,
+    pub wiki_link: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Quest {
     pub id: String,
     pub name: String,
 }
 
+/// A quest with what unlocks it and what it asks for, as progress tracking
+/// needs it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuestNode {
+    pub id: String,
+    pub name: String,
+    pub min_player_level: Option<u32>,
+    pub kappa_required: bool,
+    pub trader: Trader,
+    pub task_requirements: Vec<TaskRequirement>,
+    pub trader_requirements: Vec<TraderRequirement>,
+    pub objectives: Vec<Objective>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trader {
+    pub name: String,
+}
+
+/// Another quest that has to be in one of `status` first, usually "complete".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRequirement {
+    pub task: TaskRef,
+    pub status: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRef {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraderRequirement {
+    pub trader: Trader,
+    pub requirement_type: String,
+    pub value: u32,
+}
+
+/// Only item hand-ins carry `items`, `count` and `found_in_raid`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Objective {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub items: Vec<ObjectiveItem>,
+    #[serde(default)]
+    pub count: u32,
+    #[serde(default)]
+    pub found_in_raid: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectiveItem {
+    pub name: String,
+}
+
+/// An item as the price lookup searches for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemName {
+    pub id: String,
+    pub name: String,
+    pub short_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    pub name: String,
+    pub short_name: String,
+    pub avg_24h_price: Option<i64>,
+    pub last_low_price: Option<i64>,
+    pub change_last_48h_percent: Option<f64>,
+    pub width: u32,
+    pub height: u32,
+    pub icon_link: Option<String>,
+    pub wiki_link: Option<String>,
+    pub sell_for: Vec<ItemPrice>,
+    pub historical_prices: Vec<HistoricalPrice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemPrice {
+    #[serde(rename = "priceRUB")]
+    pub price_rub: Option<i64>,
+    pub vendor: Vendor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Vendor {
+    pub name: String,
+    pub normalized_name: String,
+}
+
+/// A flea average at a point in time, `timestamp` is milliseconds since the
+/// epoch as a string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalPrice {
+    pub price: Option<i64>,
+    pub timestamp: String,
+}
+
+/// The best of `offers` from a trader, the flea market is not one.
+fn best_trader_offer(offers: &[ItemPrice]) -> Option<&ItemPrice> {
+    offers
+        .iter()
+        .filter(|offer| offer.vendor.normalized_name != "flea-market")
+        .filter(|offer| offer.price_rub.is_some_and(|price| price > 0))
+        .max_by_key(|offer| offer.price_rub)
+}
+
+impl Item {
+    /// The best offer from a trader, the flea market is not one.
+    pub fn best_trader_sell(&self) -> Option<&ItemPrice> {
+        best_trader_offer(&self.sell_for)
+    }
+
+    pub fn slots(&self) -> u32 {
+        (self.width * self.height).max(1)
+    }
+
+    /// How far the 24h average moved since the last recorded price at least
+    /// a day before `now_ms`, in percent.
+    pub fn change_last_24h_percent(&self, now_ms: i64) -> Option<f64> {
+        let current = self.avg_24h_price.filter(|price| *price > 0)?;
+        let day_ago = now_ms - 24 * 60 * 60 * 1000;
+        let before = self
+            .historical_prices
+            .iter()
+            .filter_map(|entry| Some((entry.timestamp.parse::<i64>().ok()?, entry.price?)))
+            .filter(|(timestamp, price)| *timestamp <= day_ago && *price > 0)
+            .max_by_key(|(timestamp, _)| *timestamp)?
+            .1;
+        Some((current - before) as f64 * 100.0 / before as f64)
+    }
+}
+
+/// An item with what it costs and what it sells for, as hideout costs and
+/// craft profits need it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricedItem {
+    pub id: String,
+    pub name: String,
+    pub avg_24h_price: Option<i64>,
+    pub last_low_price: Option<i64>,
+    /// Fee for listing one on the flea market at its usual price.
+    pub flea_market_fee: Option<i64>,
+    pub buy_for: Vec<ItemPrice>,
+    pub sell_for: Vec<ItemPrice>,
+}
+
+impl PricedItem {
+    /// The cheapest way to get one, from a trader or the flea market.
+    pub fn buy_price(&self) -> Option<i64> {
+        self.buy_for
+            .iter()
+            .filter_map(|offer| offer.price_rub)
+            .filter(|price| *price > 0)
+            .min()
+            .or(self.avg_24h_price.filter(|price| *price > 0))
+    }
+
+    /// The most one brings in, from a trader or listed on the flea market
+    /// after its fee.
+    pub fn sell_value(&self) -> Option<i64> {
+        let trader = best_trader_offer(&self.sell_for).and_then(|offer| offer.price_rub);
+        let flea = self
+            .last_low_price
+            .or(self.avg_24h_price)
+            .filter(|price| *price > 0)
+            .map(|price| price - self.flea_market_fee.unwrap_or(0));
+        trader.max(flea)
+    }
+}
+
+/// An item and how many of it, in a recipe or an upgrade.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainedItem {
+    pub item: PricedItem,
+    pub count: f64,
+    #[serde(default)]
+    pub attributes: Vec<ItemAttribute>,
+}
+
+impl ContainedItem {
+    /// Tools are needed for a craft but come back afterwards.
+    pub fn is_tool(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attribute| attribute.kind == "tool")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemAttribute {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HideoutStation {
+    pub id: String,
+    pub name: String,
+    pub levels: Vec<StationLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StationLevel {
+    pub level: u32,
+    /// Seconds to build.
+    pub construction_time: u64,
+    pub item_requirements: Vec<ContainedItem>,
+    pub station_level_requirements: Vec<StationLevelRequirement>,
+    pub trader_requirements: Vec<TraderRequirement>,
+    pub skill_requirements: Vec<SkillRequirement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationRef {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationLevelRequirement {
+    pub station: StationRef,
+    pub level: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillRequirement {
+    pub name: String,
+    pub level: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Craft {
+    pub id: String,
+    pub station: StationRef,
+    pub level: u32,
+    /// Seconds the craft takes.
+    pub duration: u64,
+    pub required_items: Vec<ContainedItem>,
+    pub reward_items: Vec<ContainedItem>,
+}
+
+/// Every craft, with the fuel that keeps the generator running for them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CraftBook {
+    pub crafts: Vec<Craft>,
+    pub fuel: Option<PricedItem>,
+}
+
+/// A round as the ammo charts compare it. `recoil` is the modifier it
+/// applies to the weapon, 0.1 is 10% more recoil.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Ammo {
+    pub item: ItemName,
     pub caliber: String,
     pub damage: u64,
     pub armor_damage: u64,
     pub penetration_power: u64,
-    pub recoil: u64,
+    #[serde(rename = "recoilModifier")]
+    pub recoil: f64,
+}
+
+impl Ammo {
+    /// The caliber without the "Caliber" prefix the API gives it, "556x45NATO".
+    pub fn caliber_name(&self) -> &str {
+        self.caliber
+            .strip_prefix("Caliber")
+            .unwrap_or(&self.caliber)
+    }
 }
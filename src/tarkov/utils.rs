@@ -1,80 +1,283 @@
-use crate::tarkov::types::{Quest, Task, TaskResponse};
-use anyhow::Result;
-use reqwest::Client;
-use serde_json::json;
-use serde_json::Error;
-use std::fs;
+use crate::tarkov::hideout::CraftProfit;
+use crate::tarkov::search::Match;
+use crate::tarkov::types::{HideoutStation, Item, ItemName, Quest, StationLevel, Task};
+use serenity::all::{
+    Colour, CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind,
+    CreateSelectMenuOption,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn load_quests() -> Result<Vec<Quest>, Error> {
-    let file_content = fs::read_to_string("data/quests.json")
-        .map_err(|e| {
-            eprintln!("Failed to read JSON file: {}", e);
-            e
+/// Custom id of the menu offered when several quests match.
+pub const QUEST_MENU_ID: &str = "tarkov:quest";
+/// Custom id of the menu offered when several items match.
+pub const ITEM_MENU_ID: &str = "tarkov:item";
+/// Discord allows 25 options in a select menu.
+pub const MAX_MENU_CHOICES: usize = 25;
+
+pub fn task_embed(task: &Task) -> CreateEmbed {
+    let keys = task
+        .needed_keys
+        .iter()
+        .flat_map(|wrapper| &wrapper.keys)
+        .map(|key| {
+            let price = key
+                .avg_24h_price
+                .filter(|price| *price > 0)
+                .map_or("no flea price".to_string(), format_roubles);
+            match &key.wiki_link {
+                Some(link) => format!("[{}]({link}) {price}", key.name),
+                None => format!("{} {price}", key.name),
+            }
         })
-        .expect("Error loading JSON file: quests.json");
+        .collect::<Vec<_>>();
+    let keys = if keys.is_empty() {
+        "None".to_string()
+    } else {
+        keys.join("\n")
+    };
+    let kappa = if task.kappa_required {
+        "✅ Yes"
+    } else {
+        "❌ No"
+    };
+    let level = task
+        .min_player_level
+        .map_or("Any".to_string(), |level| level.to_string());
 
-    let quests: Vec<Quest> = serde_json::from_str(&file_content)?;
-    Ok(quests)
+    CreateEmbed::new()
+        .title(&task.name)
+        .url(&task.wiki_link)
+        .colour(Colour::DARK_GOLD)
+        .field("Kappa", kappa, true)
+        .field("Min level", level, true)
+        .field("Needed keys", keys, false)
 }
 
-pub async fn fetch_task(id: &str) -> Result<TaskResponse> {
-    let client = Client::new();
-    let query_string = format!(
-        r#"{{
-            task(id: "{}") {{
-                name
-                minPlayerLevel
-                kappaRequired
-                wikiLink
-                neededKeys {{
-                    keys {{
-                        name
-                        avg24hPrice
-                        wikiLink
-                    }}
-                }}
-            }}
-        }}"#,
-        id
-    );
-
-    let query = json!({ "query": query_string });
-    let res = client
-        .post("https://api.tarkov.dev/graphql")
-        .header("Content-Type", "application/json")
-        .json(&query)
-        .send()
-        .await?;
+/// Menu letting the user pick one of the matched quests, the value is the
+/// quest id.
+pub fn quest_menu(matches: &[Match<Quest>]) -> CreateActionRow {
+    let options = matches
+        .iter()
+        .take(MAX_MENU_CHOICES)
+        .map(|found| {
+            CreateSelectMenuOption::new(&found.item.name, &found.item.id)
+                .description(format!("{:.0}% match", found.score * 100.0))
+        })
+        .collect();
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(QUEST_MENU_ID, CreateSelectMenuKind::String { options })
+            .placeholder("Pick a quest"),
+    )
+}
 
-    let task_response: TaskResponse = res.json().await?;
+pub fn item_embed(item: &Item) -> CreateEmbed {
+    let flea = item.avg_24h_price.filter(|price| *price > 0);
+    let price = |price: Option<i64>| {
+        price
+            .filter(|price| *price > 0)
+            .map_or("Not on flea".to_string(), format_roubles)
+    };
+    let trader = item
+        .best_trader_sell()
+        .and_then(|offer| {
+            Some(format!(
+                "{} to {}",
+                format_roubles(offer.price_rub?),
+                offer.vendor.name
+            ))
+        })
+        .unwrap_or("No trader buys it".to_string());
+    let slots = item.slots();
+    // Traders are the fallback when the flea market has no price
+    let per_slot = flea
+        .or(item.best_trader_sell().and_then(|offer| offer.price_rub))
+        .map_or("Unknown".to_string(), |price| {
+            format!(
+                "{} ({}x{})",
+                format_roubles(price / slots as i64),
+                item.width,
+                item.height
+            )
+        });
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64);
 
-    Ok(task_response)
+    let mut embed = CreateEmbed::new()
+        .title(format!("{} ({})", item.name, item.short_name))
+        .colour(Colour::DARK_GOLD)
+        .field("Avg 24h flea", price(flea), true)
+        .field("Last low", price(item.last_low_price), true)
+        .field("Best trader", trader, true)
+        .field("Per slot", per_slot, true)
+        .field(
+            "24h",
+            format_change(item.change_last_24h_percent(now_ms)),
+            true,
+        )
+        .field("48h", format_change(item.change_last_48h_percent), true);
+    if let Some(link) = &item.wiki_link {
+        embed = embed.url(link);
+    }
+    if let Some(icon) = &item.icon_link {
+        embed = embed.thumbnail(icon);
+    }
+    embed
 }
 
-pub fn format_task_response(task: &Task) -> String {
-    let keys_output = if task.neededKeys.is_empty() {
-        "None".to_string()
-    } else {
-        task.neededKeys
-            .iter()
-            .flat_map(|wrapper| &wrapper.keys)
-            .map(|key| {
-                format!(
-                    "\n- **{}**\n  - avg price: {}\n    - **[Wiki Link]({})**",
-                    key.name,
-                    key.avg24hPrice.unwrap_or(0),
-                    key.wikiLink.as_deref().unwrap_or("#")
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("")
+/// What building `level` of `station` takes, priced at the cheapest offers.
+pub fn station_embed(station: &HideoutStation, level: &StationLevel) -> CreateEmbed {
+    let mut total = 0;
+    let mut unpriced = 0;
+    let items = level
+        .item_requirements
+        .iter()
+        .map(|requirement| {
+            let cost = requirement
+                .item
+                .buy_price()
+                .map(|price| (price as f64 * requirement.count).round() as i64);
+            match cost {
+                Some(cost) => total += cost,
+                None => unpriced += 1,
+            }
+            format!(
+                "{}x {} {}",
+                requirement.count,
+                requirement.item.name,
+                cost.map_or("no price".to_string(), format_roubles)
+            )
+        })
+        .collect::<Vec<_>>();
+    let stations = level
+        .station_level_requirements
+        .iter()
+        .map(|requirement| format!("{} {}", requirement.station.name, requirement.level))
+        .collect::<Vec<_>>();
+    let traders = level
+        .trader_requirements
+        .iter()
+        .filter(|requirement| requirement.requirement_type == "level")
+        .map(|requirement| format!("{} LL{}", requirement.trader.name, requirement.value))
+        .collect::<Vec<_>>();
+    let skills = level
+        .skill_requirements
+        .iter()
+        .map(|requirement| format!("{} {}", requirement.name, requirement.level))
+        .collect::<Vec<_>>();
+    // Unpriced items would only add to it, so the sum is a lower bound
+    let total = match unpriced {
+        0 => format_roubles(total),
+        1 => format!("≥ {}, 1 item has no price", format_roubles(total)),
+        n => format!("≥ {}, {n} items have no price", format_roubles(total)),
     };
+    let list = |lines: Vec<String>| {
+        if lines.is_empty() {
+            "None".to_string()
+        } else {
+            lines.join("\n")
+        }
+    };
+
+    CreateEmbed::new()
+        .title(format!("{} level {}", station.name, level.level))
+        .colour(Colour::DARK_GOLD)
+        .field("Items", list(items), false)
+        .field("Total", total, true)
+        .field("Build time", format_hours(level.construction_time), true)
+        .field("Stations", list(stations), true)
+        .field("Traders", list(traders), true)
+        .field("Skills", list(skills), true)
+}
+
+/// Crafts ranked by profit per hour, one line each.
+pub fn crafts_list(profits: &[CraftProfit]) -> String {
+    profits
+        .iter()
+        .enumerate()
+        .map(|(i, profit)| {
+            let craft = profit.craft;
+            let output = craft
+                .reward_items
+                .iter()
+                .map(|output| match output.count {
+                    count if count > 1.0 => format!("{count}x {}", output.item.name),
+                    _ => output.item.name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "{}. **{output}** ({} {}, {})\n{}/h, {} per craft: sells for {}, inputs {}, fuel {}",
+                i + 1,
+                craft.station.name,
+                craft.level,
+                format_hours(craft.duration),
+                format_roubles(profit.per_hour),
+                format_roubles(profit.profit),
+                format_roubles(profit.revenue),
+                format_roubles(profit.cost),
+                format_roubles(profit.fuel)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 12000 seconds as "3h 20m".
+pub fn format_hours(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60) {
+        (0, 0) => "Instant".to_string(),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
 
-    format!(
-        "**Quest:** {}\n\
-        **Kappa Required:** `{}`\n\
-        **Needed Keys**: {}\n\
-        **[Wiki Link]({})**",
-        task.name, task.kappaRequired, keys_output, task.wikiLink
+/// Menu letting the user pick one of the matched items, the value is the
+/// item id.
+pub fn item_menu(matches: &[Match<ItemName>]) -> CreateActionRow {
+    let options = matches
+        .iter()
+        .take(MAX_MENU_CHOICES)
+        .map(|found| {
+            CreateSelectMenuOption::new(&found.item.name, &found.item.id).description(format!(
+                "{}, {:.0}% match",
+                found.item.short_name,
+                found.score * 100.0
+            ))
+        })
+        .collect();
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(ITEM_MENU_ID, CreateSelectMenuKind::String { options })
+            .placeholder("Pick an item"),
     )
 }
+
+/// `done` out of `total` as a bar of ten segments.
+pub fn progress_bar(done: usize, total: usize) -> String {
+    let filled = (done * 10).checked_div(total).unwrap_or(0).min(10);
+    format!("{}{}", "▰".repeat(filled), "▱".repeat(10 - filled))
+}
+
+/// A price change as "📈 +4.2%", or a dash without history.
+fn format_change(percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) if percent >= 0.0 => format!("📈 +{percent:.1}%"),
+        Some(percent) => format!("📉 {percent:.1}%"),
+        None => "—".to_string(),
+    }
+}
+
+/// 1234567 as "1,234,567 ₽".
+pub fn format_roubles(amount: i64) -> String {
+    let digits = amount.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let sign = if amount < 0 { "-" } else { "" };
+    format!("{sign}{grouped} ₽")
+}
@@ -0,0 +1,62 @@
+/// Longest page of a paged reply, comfortably inside an embed.
+pub const PAGE_CHARS: usize = 1800;
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Splits `text` into pages at line breaks, each at most `max_chars` long.
+pub fn pages(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in text.lines() {
+        if !page.is_empty() && page.chars().count() + line.chars().count() + 1 > max_chars {
+            pages.push(std::mem::take(&mut page));
+        }
+        if !page.is_empty() {
+            page.push('\n');
+        }
+        page.push_str(line);
+    }
+    if !page.trim().is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edits_in_characters() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("shootin", "shooting"), 1);
+        assert_eq!(edit_distance("sávíng", "saving"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn pages_break_at_lines() {
+        let text = (1..=50)
+            .map(|n| format!("line number {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let pages = pages(&text, 100);
+        assert!(pages.len() > 1);
+        assert!(pages.iter().all(|page| page.chars().count() <= 100));
+        assert_eq!(pages.join("\n"), text);
+    }
+}